@@ -0,0 +1,464 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::gamepad::GamepadInput;
+use crate::settings;
+
+const SETTINGS_GROUP: &str = "input";
+
+/// Everything the player can do with the keyboard or a gamepad. Systems ask for actions instead of
+/// keys and buttons, so the controls can be rebound.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    /// Grab the sheep under the gamepad cursor, the mouse and touch always grab
+    Grab,
+    StartBattle,
+    NewGame,
+    Continue,
+    /// Pause the battle
+    Pause,
+    /// Undo the latest merge in the pen
+    Undo,
+    Shop,
+    /// Type a new name for the selected sheep
+    Rename,
+    /// List of all the sheep in the pen
+    Roster,
+    Options,
+    Back,
+    Mute,
+    VolumeDown,
+    VolumeUp,
+    /// Held to change the effects instead of the music with the volume hotkeys
+    AdjustEffects,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    Confirm,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 21] = [
+        Action::Grab,
+        Action::StartBattle,
+        Action::NewGame,
+        Action::Continue,
+        Action::Pause,
+        Action::Undo,
+        Action::Shop,
+        Action::Rename,
+        Action::Roster,
+        Action::Options,
+        Action::Back,
+        Action::Mute,
+        Action::VolumeDown,
+        Action::VolumeUp,
+        Action::AdjustEffects,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::MenuLeft,
+        Action::MenuRight,
+        Action::Confirm,
+        Action::Quit,
+    ];
+
+    /// Actions that can be rebound in the options menu, the rest only in the settings file
+    pub const REBINDABLE: [Action; 11] = [
+        Action::Grab,
+        Action::StartBattle,
+        Action::NewGame,
+        Action::Continue,
+        Action::Pause,
+        Action::Undo,
+        Action::Shop,
+        Action::Rename,
+        Action::Roster,
+        Action::Options,
+        Action::Mute,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Grab => "Grab",
+            Action::StartBattle => "Fight",
+            Action::NewGame => "New game",
+            Action::Continue => "Continue",
+            Action::Pause => "Pause",
+            Action::Undo => "Undo",
+            Action::Shop => "Shop",
+            Action::Rename => "Rename",
+            Action::Roster => "Roster",
+            Action::Options => "Options",
+            Action::Back => "Back",
+            Action::Mute => "Mute",
+            Action::VolumeDown => "Volume -",
+            Action::VolumeUp => "Volume +",
+            Action::AdjustEffects => "Effects",
+            Action::MenuUp => "Up",
+            Action::MenuDown => "Down",
+            Action::MenuLeft => "Left",
+            Action::MenuRight => "Right",
+            Action::Confirm => "Confirm",
+            Action::Quit => "Quit",
+        }
+    }
+
+    fn default_bindings(&self) -> Vec<Binding> {
+        use Binding::{Button, Key};
+        use GamepadButtonType as Pad;
+
+        match self {
+            Action::Grab => vec![Button(Pad::South)],
+            Action::StartBattle => vec![Key(KeyCode::Space), Button(Pad::Start)],
+            Action::NewGame => vec![Key(KeyCode::N)],
+            Action::Continue => vec![Key(KeyCode::Space), Button(Pad::South), Button(Pad::Start)],
+            Action::Pause => vec![Key(KeyCode::P), Button(Pad::Start)],
+            Action::Undo => vec![Key(KeyCode::Z), Button(Pad::North)],
+            Action::Shop => vec![Key(KeyCode::S), Button(Pad::RightTrigger)],
+            Action::Rename => vec![Key(KeyCode::R), Button(Pad::LeftTrigger)],
+            Action::Roster => vec![Key(KeyCode::Tab), Button(Pad::LeftTrigger2)],
+            Action::Options => vec![Key(KeyCode::O), Button(Pad::Select)],
+            Action::Back => vec![Key(KeyCode::Escape), Button(Pad::East)],
+            Action::Mute => vec![Key(KeyCode::M), Button(Pad::West)],
+            Action::VolumeDown => vec![Key(KeyCode::Minus)],
+            Action::VolumeUp => vec![Key(KeyCode::Equals)],
+            Action::AdjustEffects => vec![Key(KeyCode::LShift), Key(KeyCode::RShift)],
+            Action::MenuUp => vec![Key(KeyCode::Up), Button(Pad::DPadUp)],
+            Action::MenuDown => vec![Key(KeyCode::Down), Button(Pad::DPadDown)],
+            Action::MenuLeft => vec![Key(KeyCode::Left), Button(Pad::DPadLeft)],
+            Action::MenuRight => vec![Key(KeyCode::Right), Button(Pad::DPadRight)],
+            Action::Confirm => vec![Key(KeyCode::Return), Button(Pad::South)],
+            Action::Quit => vec![Key(KeyCode::Escape)],
+        }
+    }
+}
+
+/// A key or gamepad button bound to an action
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    Button(GamepadButtonType),
+}
+
+/// Keys that can be bound, with the names they are stored and shown as
+const KEY_NAMES: [(KeyCode, &str); 70] = [
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::F1, "F1"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F4, "F4"),
+    (KeyCode::F5, "F5"),
+    (KeyCode::F6, "F6"),
+    (KeyCode::F7, "F7"),
+    (KeyCode::F8, "F8"),
+    (KeyCode::F9, "F9"),
+    (KeyCode::F10, "F10"),
+    (KeyCode::F11, "F11"),
+    (KeyCode::F12, "F12"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Return, "Enter"),
+    (KeyCode::Escape, "Escape"),
+    (KeyCode::Back, "Backspace"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::Delete, "Delete"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Minus, "Minus"),
+    (KeyCode::Equals, "Equals"),
+    (KeyCode::Comma, "Comma"),
+    (KeyCode::Period, "Period"),
+    (KeyCode::Slash, "Slash"),
+    (KeyCode::LShift, "LShift"),
+    (KeyCode::RShift, "RShift"),
+    (KeyCode::LControl, "LCtrl"),
+    (KeyCode::RControl, "RCtrl"),
+];
+
+/// Gamepad buttons that can be bound, with the names they are stored and shown as
+const BUTTON_NAMES: [(GamepadButtonType, &str); 19] = [
+    (GamepadButtonType::South, "South"),
+    (GamepadButtonType::East, "East"),
+    (GamepadButtonType::North, "North"),
+    (GamepadButtonType::West, "West"),
+    (GamepadButtonType::C, "C"),
+    (GamepadButtonType::Z, "Z"),
+    (GamepadButtonType::LeftTrigger, "LeftTrigger"),
+    (GamepadButtonType::LeftTrigger2, "LeftTrigger2"),
+    (GamepadButtonType::RightTrigger, "RightTrigger"),
+    (GamepadButtonType::RightTrigger2, "RightTrigger2"),
+    (GamepadButtonType::Select, "Select"),
+    (GamepadButtonType::Start, "Start"),
+    (GamepadButtonType::Mode, "Mode"),
+    (GamepadButtonType::LeftThumb, "LeftThumb"),
+    (GamepadButtonType::RightThumb, "RightThumb"),
+    (GamepadButtonType::DPadUp, "DPadUp"),
+    (GamepadButtonType::DPadDown, "DPadDown"),
+    (GamepadButtonType::DPadLeft, "DPadLeft"),
+    (GamepadButtonType::DPadRight, "DPadRight"),
+];
+
+impl Binding {
+    /// Name in the settings, `None` if the key or button can't be bound
+    fn name(&self) -> Option<&'static str> {
+        match self {
+            Binding::Key(key) => KEY_NAMES
+                .iter()
+                .find(|(bindable, _)| bindable == key)
+                .map(|(_, name)| *name),
+            Binding::Button(button) => BUTTON_NAMES
+                .iter()
+                .find(|(bindable, _)| bindable == button)
+                .map(|(_, name)| *name),
+        }
+    }
+
+    /// Name shown to the player, e.g. `SPACE` or `PAD START`
+    pub fn label(&self) -> String {
+        let name = self.name().unwrap_or("???");
+        match self {
+            Binding::Key(_) => name.to_uppercase(),
+            Binding::Button(_) => format!("PAD {name}").to_uppercase(),
+        }
+    }
+
+    fn is_bindable(&self) -> bool {
+        self.name().is_some()
+    }
+
+    fn same_device(&self, other: &Binding) -> bool {
+        matches!(
+            (self, other),
+            (Binding::Key(_), Binding::Key(_)) | (Binding::Button(_), Binding::Button(_))
+        )
+    }
+
+    /// Stored as `Key:Space` or `Button:Start`
+    fn to_setting(self) -> Option<String> {
+        let name = self.name()?;
+        match self {
+            Binding::Key(_) => Some(format!("Key:{name}")),
+            Binding::Button(_) => Some(format!("Button:{name}")),
+        }
+    }
+
+    fn from_setting(setting: &str) -> Option<Self> {
+        match setting.trim().split_once(':')? {
+            ("Key", name) => KEY_NAMES
+                .iter()
+                .find(|(_, key_name)| *key_name == name)
+                .map(|(key, _)| Binding::Key(*key)),
+            ("Button", name) => BUTTON_NAMES
+                .iter()
+                .find(|(_, button_name)| *button_name == name)
+                .map(|(button, _)| Binding::Button(*button)),
+            _ => None,
+        }
+    }
+}
+
+/// Which keys and buttons trigger each action, stored in the settings
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_bindings()))
+                .collect(),
+        }
+    }
+}
+
+impl InputMap {
+    /// Loads the stored bindings, actions that aren't stored keep their defaults
+    pub fn load() -> Self {
+        let mut input_map = Self::default();
+
+        if let Some(contents) = settings::load(SETTINGS_GROUP) {
+            let values = settings::parse(&contents);
+            for action in Action::ALL {
+                if let Some(value) = values.get(format!("{action:?}").as_str()) {
+                    let bindings = value.split(',').filter_map(Binding::from_setting).collect();
+                    input_map.bindings.insert(action, bindings);
+                }
+            }
+        }
+
+        input_map
+    }
+
+    pub fn save(&self) {
+        let contents = Action::ALL
+            .iter()
+            .map(|action| {
+                let bindings = self
+                    .bindings(*action)
+                    .iter()
+                    .filter_map(|binding| binding.to_setting())
+                    .collect::<Vec<_>>();
+                format!("{action:?}={}\n", bindings.join(","))
+            })
+            .collect::<String>();
+        settings::save(SETTINGS_GROUP, &contents);
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Name of the first binding, to tell the player what to press
+    pub fn label(&self, action: Action) -> String {
+        self.bindings(action)
+            .first()
+            .map_or_else(|| "???".to_string(), Binding::label)
+    }
+
+    /// Replaces the bindings of the `action` on the same device as the new `binding`, the other
+    /// device keeps its bindings. Returns `false` if the key or button can't be bound.
+    pub fn rebind(&mut self, action: Action, binding: Binding) -> bool {
+        if !binding.is_bindable() {
+            return false;
+        }
+
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|bound| !bound.same_device(&binding));
+        bindings.push(binding);
+        true
+    }
+}
+
+/// If this resource is present, the keyboard is used to type text. Only `Confirm` and `Back` are
+/// reported, so typing doesn't trigger any of the other actions.
+pub struct TextInput;
+
+/// Access to the state of the actions, combining the keyboard and all the gamepads
+#[derive(SystemParam)]
+pub struct Actions<'w, 's> {
+    input_map: Res<'w, InputMap>,
+    keys: Res<'w, Input<KeyCode>>,
+    gamepad: GamepadInput<'w, 's>,
+    text_input: Option<Res<'w, TextInput>>,
+}
+
+impl<'w, 's> Actions<'w, 's> {
+    fn typing_ignores(&self, action: Action) -> bool {
+        self.text_input.is_some() && !matches!(action, Action::Confirm | Action::Back)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        if self.typing_ignores(action) {
+            return false;
+        }
+        self.input_map
+            .bindings(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => self.keys.pressed(*key),
+                Binding::Button(button) => self.gamepad.pressed(*button),
+            })
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        if self.typing_ignores(action) {
+            return false;
+        }
+        self.input_map
+            .bindings(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => self.keys.just_pressed(*key),
+                Binding::Button(button) => self.gamepad.just_pressed(*button),
+            })
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        if self.typing_ignores(action) {
+            return false;
+        }
+        self.input_map
+            .bindings(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => self.keys.just_released(*key),
+                Binding::Button(button) => self.gamepad.just_released(*button),
+            })
+    }
+
+    pub fn any_just_released(&self, actions: impl IntoIterator<Item = Action>) -> bool {
+        actions.into_iter().any(|action| self.just_released(action))
+    }
+
+    /// Any key or button released this frame, used to rebind an action
+    pub fn released_binding(&self) -> Option<Binding> {
+        self.keys
+            .get_just_released()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| self.gamepad.released_button().map(Binding::Button))
+    }
+
+    pub fn input_map(&self) -> &InputMap {
+        &self.input_map
+    }
+}
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputMap::load())
+            .add_system(save_input_map);
+    }
+}
+
+fn save_input_map(input_map: Res<InputMap>) {
+    if input_map.is_changed() && !input_map.is_added() {
+        input_map.save();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::GameState;
+use crate::battle::battle_running;
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 use iyes_loopless::prelude::*;
@@ -10,7 +10,7 @@ impl Plugin for AnimationPlugin {
         app.add_system_set_to_stage(
             CoreStage::Last,
             ConditionSet::new()
-                .run_in_state(GameState::Battle)
+                .run_if(battle_running)
                 .with_system(animate)
                 .into(),
         );
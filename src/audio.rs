@@ -1,78 +1,156 @@
 use bevy::{prelude::*, utils::HashMap};
-use bevy_kira_audio::{AudioApp, AudioChannel};
+use bevy_kira_audio::AudioApp;
 use iyes_loopless::prelude::*;
 
 use crate::animation::Animation;
-use crate::GameState;
+use crate::{settings, GameState};
+pub use cues::{AudioListener, PlaySound, SoundCue};
 
-pub struct MusicChannel;
+use cues::{play_sound_cues, EffectsChannel, SoundCues};
+use music::{
+    crossfade_music, play_battle_music, play_battle_report_stinger, play_herding_music,
+    stop_stinger, update_stinger, MusicFadeChannel, MusicFader, PlayMusic, StingerChannel,
+    StingerTimer,
+};
 
-pub struct EffectsChannel;
+mod cues;
+mod music;
 
 pub struct AudioPlugin;
 
+/// Volume settings of the audio channels, persisted whenever they change
+#[derive(Clone, PartialEq)]
+pub struct AudioSettings {
+    pub music_volume: f32,
+    pub music_muted: bool,
+    pub effects_volume: f32,
+    pub effects_muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            music_volume: 1.0,
+            music_muted: false,
+            effects_volume: 0.8,
+            effects_muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    const SETTINGS_GROUP: &'static str = "audio";
+
+    /// Loads the stored settings, falling back to defaults for anything missing
+    pub fn load() -> Self {
+        let mut audio_settings = Self::default();
+        let stored = settings::load(Self::SETTINGS_GROUP).unwrap_or_default();
+        let values = settings::parse(&stored);
+
+        let volume = |key: &str| values.get(key).and_then(|v| v.parse::<f32>().ok());
+        let muted = |key: &str| values.get(key).and_then(|v| v.parse::<bool>().ok());
+
+        if let Some(volume) = volume("music_volume") {
+            audio_settings.music_volume = volume.clamp(0.0, 1.0);
+        }
+        if let Some(muted) = muted("music_muted") {
+            audio_settings.music_muted = muted;
+        }
+        if let Some(volume) = volume("effects_volume") {
+            audio_settings.effects_volume = volume.clamp(0.0, 1.0);
+        }
+        if let Some(muted) = muted("effects_muted") {
+            audio_settings.effects_muted = muted;
+        }
+
+        audio_settings
+    }
+
+    pub fn save(&self) {
+        settings::save(
+            Self::SETTINGS_GROUP,
+            &format!(
+                "music_volume={}\nmusic_muted={}\neffects_volume={}\neffects_muted={}\n",
+                self.music_volume, self.music_muted, self.effects_volume, self.effects_muted
+            ),
+        );
+    }
+
+    /// Volume the `MusicChannel` should be playing at
+    pub fn music(&self) -> f32 {
+        if self.music_muted {
+            0.0
+        } else {
+            self.music_volume
+        }
+    }
+
+    /// Volume the `EffectsChannel` should be playing at
+    pub fn effects(&self) -> f32 {
+        if self.effects_muted {
+            0.0
+        } else {
+            self.effects_volume
+        }
+    }
+}
+
 /// For readability.
 const IMPOSSIBLE_ANIMATION_I: usize = usize::MAX;
 
 impl Plugin for AudioPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(bevy_kira_audio::AudioPlugin)
-            .add_audio_channel::<MusicChannel>()
-            .add_audio_channel::<EffectsChannel>()
-            .add_startup_system(set_audio_channels_volume)
+            .add_audio_channel::<music::MusicChannel>()
+            .add_audio_channel::<MusicFadeChannel>()
+            .add_audio_channel::<StingerChannel>()
+            .add_audio_channel::<EffectsChannel<0>>()
+            .add_audio_channel::<EffectsChannel<1>>()
+            .add_audio_channel::<EffectsChannel<2>>()
+            .add_audio_channel::<EffectsChannel<3>>()
+            .add_event::<PlayMusic>()
+            .add_event::<PlaySound>()
+            .insert_resource(AudioSettings::load())
+            .init_resource::<MusicFader>()
+            .init_resource::<SoundCues>()
+            .add_system(save_audio_settings)
+            .add_system(crossfade_music)
+            .add_system(update_stinger.run_if_resource_exists::<StingerTimer>())
             .add_enter_system(GameState::Herding, play_herding_music)
-            .add_exit_system(GameState::Herding, stop_herding_music)
-            .add_enter_system(GameState::Battle, play_battle_music)
-            .add_exit_system(GameState::Battle, stop_battle_music)
+            .add_enter_system(GameState::Deployment, play_battle_music)
+            .add_enter_system(GameState::BattleReport, play_battle_report_stinger)
+            .add_exit_system(GameState::BattleReport, stop_stinger)
             .add_system_to_stage(
                 CoreStage::PostUpdate,
                 animation_audio_playback.run_in_state(GameState::Battle),
-            );
+            )
+            .add_system_to_stage(CoreStage::Last, play_sound_cues);
     }
 }
 
-pub fn set_audio_channels_volume(
-    music_channel: Res<AudioChannel<MusicChannel>>,
-    effects_channel: Res<AudioChannel<EffectsChannel>>,
-) {
-    music_channel.set_volume(1.0);
-    effects_channel.set_volume(0.8);
-}
-
-pub fn play_herding_music(
-    asset_server: Res<AssetServer>,
-    music_channel: Res<AudioChannel<MusicChannel>>,
-) {
-    let battle_music = asset_server.load("audio/sheep_herding.mp3");
-    music_channel.play_looped(battle_music);
-}
-
-pub fn stop_herding_music(music_channel: Res<AudioChannel<MusicChannel>>) {
-    music_channel.stop();
-}
-
-pub fn play_battle_music(
-    asset_server: Res<AssetServer>,
-    music_channel: Res<AudioChannel<MusicChannel>>,
-) {
-    let battle_music = asset_server.load("audio/war_machines_attacking.mp3");
-    music_channel.play_looped(battle_music);
-}
+/// Persists the `AudioSettings` whenever they change. They are applied by `crossfade_music` and
+/// `play_sound_cues`.
+pub fn save_audio_settings(audio_settings: Res<AudioSettings>) {
+    if !audio_settings.is_changed() {
+        return;
+    }
 
-pub fn stop_battle_music(music_channel: Res<AudioChannel<MusicChannel>>) {
-    music_channel.stop();
+    // Settings were just loaded on startup, nothing new to store
+    if !audio_settings.is_added() {
+        audio_settings.save();
+    }
 }
 
-/// Add this to a sprite, when want to play sound effects attached to certain animation indexes.
+/// Add this to a sprite, when want to play sound cues attached to certain animation indexes.
 #[derive(Component)]
 pub struct AnimationAudioPlayback {
     pub animation_name: String,
-    pub effects: HashMap<usize, String>,
+    pub effects: HashMap<usize, SoundCue>,
     pub last_played: Option<usize>,
 }
 
 impl AnimationAudioPlayback {
-    pub fn new(animation_name: String, effects: HashMap<usize, String>) -> Self {
+    pub fn new(animation_name: String, effects: HashMap<usize, SoundCue>) -> Self {
         Self {
             animation_name,
             effects,
@@ -83,11 +161,10 @@ impl AnimationAudioPlayback {
 
 pub fn animation_audio_playback(
     mut commands: Commands,
-    mut query: Query<(Entity, &Animation, &mut AnimationAudioPlayback)>,
-    effects_channel: Res<AudioChannel<EffectsChannel>>,
-    asset_server: Res<AssetServer>,
+    mut query: Query<(Entity, &Animation, &Transform, &mut AnimationAudioPlayback)>,
+    mut play_sound: EventWriter<PlaySound>,
 ) {
-    for (entity, animation, mut state_effects) in query.iter_mut() {
+    for (entity, animation, transform, mut state_effects) in query.iter_mut() {
         if animation.current_animation.as_ref() != Some(&state_effects.animation_name) {
             commands.entity(entity).remove::<AnimationAudioPlayback>();
 
@@ -95,11 +172,11 @@ pub fn animation_audio_playback(
         }
 
         if let Some(_) = &animation.current_animation {
-            if let Some(audio_file) = state_effects.effects.get(&animation.current_frame) {
+            if let Some(cue) = state_effects.effects.get(&animation.current_frame) {
                 if state_effects.last_played.unwrap_or(IMPOSSIBLE_ANIMATION_I)
                     != animation.current_frame
                 {
-                    effects_channel.play(asset_server.load(audio_file));
+                    play_sound.send(PlaySound::at(*cue, transform.translation.truncate()));
                     state_effects.last_played = Some(animation.current_frame);
                 }
             }
@@ -0,0 +1,252 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_kira_audio::{AudioChannel, InstanceHandle, PlaybackState};
+use rand::{thread_rng, Rng};
+
+use super::AudioSettings;
+
+/// Effects are spread over a few channels, since volume and pitch can only be set per channel.
+/// A cue only plays on a lane that is idle, so changing them never retunes a sound still playing.
+pub struct EffectsChannel<const LANE: usize>;
+
+pub const EFFECTS_LANES: usize = 4;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SoundCue {
+    SheepPickup,
+    SheepDrop,
+    SheepMerge,
+    SheepHit,
+    SheepDeath,
+    SheepRankUp,
+    WarMachineSpotted,
+    WarMachineEat,
+    TimerWarning,
+}
+
+/// How a cue sounds and how often it may be played
+pub struct CueDefinition {
+    pub path: &'static str,
+    pub volume: f32,
+    pub playback_rate: f32,
+    /// The playback rate is randomly changed by up to this much on every play
+    pub pitch_variation: f32,
+    /// Plays of the same cue within this time are dropped
+    pub cooldown_secs: f64,
+}
+
+impl SoundCue {
+    pub fn definition(&self) -> CueDefinition {
+        match self {
+            SoundCue::SheepPickup => CueDefinition {
+                path: "audio/sheep_pickup.wav",
+                volume: 0.3,
+                playback_rate: 1.0,
+                pitch_variation: 0.15,
+                cooldown_secs: 0.1,
+            },
+            SoundCue::SheepDrop => CueDefinition {
+                path: "audio/sheep_drop.wav",
+                volume: 0.3,
+                playback_rate: 1.0,
+                pitch_variation: 0.15,
+                cooldown_secs: 0.1,
+            },
+            SoundCue::SheepMerge => CueDefinition {
+                path: "audio/sheep_merge.wav",
+                volume: 0.5,
+                playback_rate: 1.0,
+                pitch_variation: 0.1,
+                cooldown_secs: 0.2,
+            },
+            SoundCue::SheepHit => CueDefinition {
+                path: "audio/sheep_hit.wav",
+                volume: 0.25,
+                playback_rate: 1.0,
+                pitch_variation: 0.2,
+                cooldown_secs: 0.25,
+            },
+            SoundCue::SheepDeath => CueDefinition {
+                path: "audio/sheep_death.wav",
+                volume: 0.6,
+                playback_rate: 1.0,
+                pitch_variation: 0.1,
+                cooldown_secs: 0.3,
+            },
+            SoundCue::SheepRankUp => CueDefinition {
+                path: "audio/sheep_rank_up.wav",
+                volume: 0.5,
+                playback_rate: 1.0,
+                pitch_variation: 0.0,
+                cooldown_secs: 0.2,
+            },
+            SoundCue::WarMachineSpotted => CueDefinition {
+                path: "audio/robot_engaged.mp3",
+                volume: 0.5,
+                playback_rate: 1.0,
+                pitch_variation: 0.1,
+                cooldown_secs: 4.0,
+            },
+            SoundCue::WarMachineEat => CueDefinition {
+                path: "audio/robot_eat.mp3",
+                volume: 1.0,
+                playback_rate: 1.0,
+                pitch_variation: 0.1,
+                cooldown_secs: 0.15,
+            },
+            SoundCue::TimerWarning => CueDefinition {
+                path: "audio/timer_warning.wav",
+                volume: 0.4,
+                playback_rate: 1.0,
+                pitch_variation: 0.0,
+                cooldown_secs: 0.5,
+            },
+        }
+    }
+}
+
+/// Emitters further away from the listener than the visible half width are this quiet
+const MIN_POSITIONAL_VOLUME: f32 = 0.35;
+/// How far left or right the sound can be panned, `1.0` is fully to one side
+const MAX_PANNING: f32 = 0.8;
+
+/// Marks the camera the positional effects are heard from
+#[derive(Component)]
+pub struct AudioListener;
+
+/// Send this event to play a sound cue on the effects channels
+pub struct PlaySound {
+    pub cue: SoundCue,
+    /// World position of the emitter, the sound is not panned or attenuated if `None`
+    pub position: Option<Vec2>,
+}
+
+impl PlaySound {
+    pub fn new(cue: SoundCue) -> Self {
+        Self {
+            cue,
+            position: None,
+        }
+    }
+
+    pub fn at(cue: SoundCue, position: Vec2) -> Self {
+        Self {
+            cue,
+            position: Some(position),
+        }
+    }
+}
+
+/// Returns the `(volume, panning)` of an emitter at `offset` from the listener, which can see
+/// `half_width` world units to each side.
+fn positional(offset: Vec2, half_width: f32) -> (f32, f32) {
+    if half_width <= 0.0 {
+        return (1.0, 0.5);
+    }
+
+    let distance = (offset.length() / half_width).clamp(0.0, 1.0);
+    let volume = 1.0 + (MIN_POSITIONAL_VOLUME - 1.0) * distance;
+    let panning = 0.5 + 0.5 * MAX_PANNING * (offset.x / half_width).clamp(-1.0, 1.0);
+
+    (volume, panning)
+}
+
+/// Keeps track of when the cues were played last and what each lane is playing
+#[derive(Default)]
+pub struct SoundCues {
+    last_played: HashMap<SoundCue, f64>,
+    lanes: [Option<InstanceHandle>; EFFECTS_LANES],
+}
+
+/// Whether the last sound played on the lane has finished
+fn is_idle<const LANE: usize>(
+    channel: &AudioChannel<EffectsChannel<LANE>>,
+    instance: &Option<InstanceHandle>,
+) -> bool {
+    instance.as_ref().map_or(true, |handle| {
+        matches!(channel.state(handle.clone()), PlaybackState::Stopped)
+    })
+}
+
+fn play_on_lane<const LANE: usize>(
+    channel: &AudioChannel<EffectsChannel<LANE>>,
+    sound: Handle<bevy_kira_audio::AudioSource>,
+    volume: f32,
+    playback_rate: f32,
+    panning: f32,
+) -> InstanceHandle {
+    channel.set_volume(volume);
+    channel.set_playback_rate(playback_rate);
+    channel.set_panning(panning);
+    channel.play(sound)
+}
+
+/// Plays the requested cues, dropping the ones still on cooldown, muted or without an idle lane
+pub fn play_sound_cues(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    audio_settings: Res<AudioSettings>,
+    mut cues: ResMut<SoundCues>,
+    mut play_sound: EventReader<PlaySound>,
+    listener_q: Query<(&Transform, &OrthographicProjection), With<AudioListener>>,
+    lane0: Res<AudioChannel<EffectsChannel<0>>>,
+    lane1: Res<AudioChannel<EffectsChannel<1>>>,
+    lane2: Res<AudioChannel<EffectsChannel<2>>>,
+    lane3: Res<AudioChannel<EffectsChannel<3>>>,
+) {
+    let mut rng = thread_rng();
+    let now = time.seconds_since_startup();
+
+    let listener = listener_q.get_single().ok().map(|(transform, projection)| {
+        (
+            transform.translation.truncate(),
+            (projection.right - projection.left) * projection.scale / 2.0,
+        )
+    });
+
+    for PlaySound { cue, position } in play_sound.iter() {
+        let definition = cue.definition();
+
+        if let Some(last_played) = cues.last_played.get(cue) {
+            if now - last_played < definition.cooldown_secs {
+                continue;
+            }
+        }
+
+        if audio_settings.effects() <= 0.0 {
+            continue;
+        }
+
+        let (positional_volume, panning) = match (position, listener) {
+            (Some(position), Some((listener_position, half_width))) => {
+                positional(*position - listener_position, half_width)
+            }
+            _ => (1.0, 0.5),
+        };
+
+        let volume = definition.volume * positional_volume * audio_settings.effects();
+        let playback_rate = definition.playback_rate
+            * (1.0 + rng.gen_range(-definition.pitch_variation..=definition.pitch_variation));
+        let sound = asset_server.load(definition.path);
+
+        let idle = [
+            is_idle(&lane0, &cues.lanes[0]),
+            is_idle(&lane1, &cues.lanes[1]),
+            is_idle(&lane2, &cues.lanes[2]),
+            is_idle(&lane3, &cues.lanes[3]),
+        ];
+        // The cue is dropped if every lane is still busy
+        let lane = match idle.iter().position(|idle| *idle) {
+            Some(lane) => lane,
+            None => continue,
+        };
+
+        cues.lanes[lane] = Some(match lane {
+            0 => play_on_lane(&lane0, sound, volume, playback_rate, panning),
+            1 => play_on_lane(&lane1, sound, volume, playback_rate, panning),
+            2 => play_on_lane(&lane2, sound, volume, playback_rate, panning),
+            _ => play_on_lane(&lane3, sound, volume, playback_rate, panning),
+        });
+        // Dropped cues don't start their cooldown
+        cues.last_played.insert(*cue, now);
+    }
+}
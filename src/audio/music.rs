@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+use bevy_kira_audio::AudioChannel;
+
+use super::AudioSettings;
+use crate::battle_report::{BattleResult, BattleStatus};
+
+const CROSSFADE_SECS: f32 = 1.5;
+/// Stingers are faded out instead of being cut off
+const STINGER_FADE_SECS: f32 = 1.0;
+
+pub struct MusicChannel;
+
+/// Second music channel, so the outgoing track can fade out while the next one fades in
+pub struct MusicFadeChannel;
+
+/// Short one-shot tunes played on top of the music, e.g. after the battle
+pub struct StingerChannel;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MusicTrack {
+    Herding,
+    Battle,
+}
+
+impl MusicTrack {
+    fn path(&self) -> &'static str {
+        match self {
+            MusicTrack::Herding => "audio/sheep_herding.mp3",
+            MusicTrack::Battle => "audio/war_machines_attacking.mp3",
+        }
+    }
+}
+
+/// Send this event to crossfade into another track, `None` fades out the music
+pub struct PlayMusic(pub Option<MusicTrack>);
+
+/// Keeps track of which music channel is playing the current track and how far the crossfade
+/// between the channels is.
+pub struct MusicFader {
+    /// Index of the channel with the current track, `0` is `MusicChannel`, `1` is
+    /// `MusicFadeChannel`
+    active: usize,
+    track: Option<MusicTrack>,
+    /// Relative volumes of the channels when the fade started
+    from: [f32; 2],
+    volumes: [f32; 2],
+    fade: Timer,
+    /// Running while the stinger fades out
+    stinger_fade: Option<Timer>,
+}
+
+impl Default for MusicFader {
+    fn default() -> Self {
+        Self {
+            active: 0,
+            track: None,
+            from: [0.0; 2],
+            volumes: [0.0; 2],
+            fade: Timer::from_seconds(CROSSFADE_SECS, false),
+            stinger_fade: None,
+        }
+    }
+}
+
+impl MusicFader {
+    fn target(&self, channel: usize) -> f32 {
+        match self.track {
+            Some(_) if channel == self.active => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    fn fade_out_stinger(&mut self) {
+        self.stinger_fade = Some(Timer::from_seconds(STINGER_FADE_SECS, false));
+    }
+}
+
+pub fn play_herding_music(mut play_music: EventWriter<PlayMusic>) {
+    play_music.send(PlayMusic(Some(MusicTrack::Herding)));
+}
+
+pub fn play_battle_music(mut play_music: EventWriter<PlayMusic>) {
+    play_music.send(PlayMusic(Some(MusicTrack::Battle)));
+}
+
+/// Starts requested tracks on the free channel and fades the channels and the stinger in and out
+#[allow(clippy::too_many_arguments)]
+pub fn crossfade_music(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    audio_settings: Res<AudioSettings>,
+    mut fader: ResMut<MusicFader>,
+    mut play_music: EventReader<PlayMusic>,
+    music_channel: Res<AudioChannel<MusicChannel>>,
+    fade_channel: Res<AudioChannel<MusicFadeChannel>>,
+    stinger_channel: Res<AudioChannel<StingerChannel>>,
+) {
+    if let Some(stinger_fade) = fader.stinger_fade.as_mut() {
+        let progress = stinger_fade.tick(time.delta()).percent();
+        stinger_channel.set_volume((1.0 - progress) * audio_settings.music());
+        if stinger_fade.finished() {
+            stinger_channel.stop();
+            fader.stinger_fade = None;
+        }
+    }
+
+    for PlayMusic(track) in play_music.iter() {
+        if *track == fader.track {
+            continue;
+        }
+
+        fader.from = fader.volumes;
+        fader.track = *track;
+        fader.fade.reset();
+
+        if let Some(track) = track {
+            let incoming = 1 - fader.active;
+            let music = asset_server.load(track.path());
+            match incoming {
+                0 => {
+                    music_channel.stop();
+                    music_channel.play_looped(music);
+                }
+                _ => {
+                    fade_channel.stop();
+                    fade_channel.play_looped(music);
+                }
+            }
+
+            fader.active = incoming;
+            fader.from[incoming] = 0.0;
+        }
+    }
+
+    let fading = !fader.fade.finished();
+    if !fading && !audio_settings.is_changed() {
+        return;
+    }
+
+    fader.fade.tick(time.delta());
+    let progress = fader.fade.percent();
+    for channel in 0..2 {
+        fader.volumes[channel] =
+            fader.from[channel] + (fader.target(channel) - fader.from[channel]) * progress;
+    }
+
+    music_channel.set_volume(fader.volumes[0] * audio_settings.music());
+    fade_channel.set_volume(fader.volumes[1] * audio_settings.music());
+
+    // Stop whatever was faded out, so it doesn't keep on playing silently
+    if fader.fade.just_finished() {
+        if fader.target(0) == 0.0 {
+            music_channel.stop();
+        }
+        if fader.target(1) == 0.0 {
+            fade_channel.stop();
+        }
+    }
+}
+
+/// A short tune for the battle result
+struct Stinger {
+    path: &'static str,
+    duration_secs: f32,
+}
+
+impl Stinger {
+    fn for_status(status: &BattleStatus) -> Option<Self> {
+        match status {
+            BattleStatus::Victory => Some(Self {
+                path: "audio/stinger_victory.wav",
+                duration_secs: 1.35,
+            }),
+            BattleStatus::Draw => Some(Self {
+                path: "audio/stinger_draw.wav",
+                duration_secs: 1.65,
+            }),
+            BattleStatus::GameOver => Some(Self {
+                path: "audio/stinger_game_over.wav",
+                duration_secs: 2.45,
+            }),
+            BattleStatus::StillPlaying => None,
+        }
+    }
+}
+
+/// If this resource is present, a stinger is playing and it fades out once the timer ends
+pub struct StingerTimer(Timer);
+
+/// Fades out the battle music and plays the stinger matching the battle result
+pub fn play_battle_report_stinger(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    audio_settings: Res<AudioSettings>,
+    battle_result: Option<Res<BattleResult>>,
+    mut fader: ResMut<MusicFader>,
+    mut play_music: EventWriter<PlayMusic>,
+    stinger_channel: Res<AudioChannel<StingerChannel>>,
+) {
+    play_music.send(PlayMusic(None));
+    fader.stinger_fade = None;
+
+    if let Some(stinger) =
+        battle_result.and_then(|result| Stinger::for_status(&result.battle_status))
+    {
+        stinger_channel.stop();
+        stinger_channel.set_volume(audio_settings.music());
+        stinger_channel.play(asset_server.load(stinger.path));
+
+        commands.insert_resource(StingerTimer(Timer::from_seconds(
+            stinger.duration_secs,
+            false,
+        )));
+    }
+}
+
+pub fn update_stinger(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut stinger_timer: ResMut<StingerTimer>,
+    mut fader: ResMut<MusicFader>,
+) {
+    if stinger_timer.0.tick(time.delta()).finished() {
+        fader.fade_out_stinger();
+        commands.remove_resource::<StingerTimer>();
+    }
+}
+
+/// Leaving the battle report fades out a stinger that is still playing
+pub fn stop_stinger(
+    mut commands: Commands,
+    stinger_timer: Option<Res<StingerTimer>>,
+    mut fader: ResMut<MusicFader>,
+) {
+    if stinger_timer.is_some() {
+        fader.fade_out_stinger();
+        commands.remove_resource::<StingerTimer>();
+    }
+}
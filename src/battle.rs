@@ -1,21 +1,30 @@
 use bevy::prelude::*;
 use iyes_loopless::prelude::*;
 
+use crate::actions::{Action, Actions};
+use crate::audio::{PlaySound, SoundCue};
 use crate::battle_report::{BattleResult, BattleStatus};
+use crate::dialog::no_modal_open;
 use crate::sheep::{self};
-use crate::utils::{
-    bounds_check, despawn_entities_with_component, Attack, BehaviourType, Health, Speed,
-    UnloadOnExit,
-};
+use crate::utils::{bounds_check, despawn_entities_with_component, Attack, Health, UnloadOnExit};
 use rand::{thread_rng, Rng};
 
-use crate::ui::{write_text, AsciiSheet};
+use crate::ui::{write_hud_text, Anchor, AsciiSheet};
 use crate::GameState;
+use archetypes::Archetype;
+use damage::DamageEvent;
+use deployment::WAR_MACHINE_ZONE_X;
 use health_bars::{create_sheep_hp_bar, update_health_bars};
+use status::StatusEffects;
 use war_machines::{new_war_machine, WarMachine};
 
-mod health_bars;
+pub mod archetypes;
+pub mod boss;
+pub mod damage;
+pub mod deployment;
+pub mod health_bars;
 mod states;
+pub mod status;
 pub mod war_machines;
 
 /// Resource for keeping battle timer, after it runs out, there is a tie
@@ -28,6 +37,13 @@ pub struct BattleTimerText;
 #[derive(PartialEq)]
 pub struct Level(pub usize);
 
+/// If this resource is present, the battle is paused
+pub struct Paused;
+
+/// Marker component for the pause text
+#[derive(Component)]
+struct PausedText;
+
 pub const BATTLEFIELD_BOUNDS_X: Vec2 = Vec2::new(-6.2, 6.2);
 pub const BATTLEFIELD_BOUNDS_Y: Vec2 = Vec2::new(-6.4, 7.0);
 
@@ -35,15 +51,23 @@ pub const DEFAULT_ROUND_TIME: f32 = 15.0;
 
 pub const MAX_LEVEL: usize = 4;
 
+/// Sheep earn experience for the damage they deal and a bonus for destroying a war machine
+const EXPERIENCE_PER_DAMAGE: f32 = 1.0;
+const EXPERIENCE_PER_KILL: f32 = 20.0;
+
+/// During the last seconds of the battle, a warning sound plays every second
+pub const TIMER_WARNING_SECS: f32 = 5.0;
+
 pub struct BattlePlugin;
 
 impl Plugin for BattlePlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(
             ConditionSet::new()
-                .run_in_state(GameState::Battle)
+                .run_if(battle_running)
                 .label("update")
                 .with_system(sheep_attack)
+                .with_system(sheep::rank_up_sheep)
                 .with_system(update_health_bars)
                 .with_system(remove_dead_sheep)
                 .with_system(sheep::wander)
@@ -54,49 +78,79 @@ impl Plugin for BattlePlugin {
         )
         .add_system_set(
             ConditionSet::new()
-                .run_in_state(GameState::Battle)
+                .run_if(battle_running)
                 .after("update")
                 .with_system(bounds_check)
                 .with_system(apply_dying_to_dead_war_machines)
                 .with_system(check_end_battle)
                 .into(),
         )
+        .add_system(
+            toggle_pause
+                .run_in_state(GameState::Battle)
+                .run_if(no_modal_open),
+        )
+        // The battlefield is set up when deploying, so the war machines can be seen already
         .add_enter_system_set(
-            GameState::Battle,
+            GameState::Deployment,
             ConditionSet::new()
                 .with_system(setup_level1.run_if_resource_equals::<Level>(Level(1)))
                 .with_system(setup_level2.run_if_resource_equals::<Level>(Level(2)))
                 .with_system(setup_level3.run_if_resource_equals::<Level>(Level(3)))
                 .with_system(setup_level4.run_if_resource_equals::<Level>(Level(4)))
-                .with_system(add_health_bars_to_sheep)
                 .with_system(setup_ui)
                 .into(),
         )
+        .add_enter_system_set(
+            GameState::Battle,
+            ConditionSet::new()
+                .with_system(add_health_bars_to_sheep)
+                .with_system(count_battles)
+                .into(),
+        )
         .add_exit_system_set(
             GameState::Battle,
             ConditionSet::new()
                 .with_system(despawn_entities_with_component::<UnloadOnExit>)
+                .with_system(unpause)
                 .into(),
         );
     }
 }
 
+/// Run condition for every system that moves the battle on, they all stop while it is paused
+pub fn battle_running(state: Res<CurrentState<GameState>>, paused: Option<Res<Paused>>) -> bool {
+    state.0 == GameState::Battle && paused.is_none()
+}
+
 fn add_health_bars_to_sheep(mut commands: Commands, sheep_q: Query<Entity, With<sheep::Sheep>>) {
     sheep_q.for_each(|sheep| create_sheep_hp_bar(sheep, &mut commands));
 }
 
+/// Every sheep that is not on the bench remembers the battle
+fn count_battles(mut sheep_q: Query<&mut sheep::Sheep>) {
+    sheep_q.for_each_mut(|mut sheep| sheep.join_battle());
+}
+
 fn sheep_attack(
-    mut sheep_q: Query<(&mut Transform, &Attack), (With<sheep::Sheep>, Without<WarMachine>)>,
+    mut sheep_q: Query<(Entity, &mut sheep::Sheep, &mut Transform, &Attack), Without<WarMachine>>,
     mut war_machines_q: Query<
-        (&mut Health, &mut Transform),
+        (Entity, &mut Health, &mut StatusEffects, &mut Transform),
         (With<WarMachine>, Without<sheep::Sheep>),
     >,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut rank_ups: EventWriter<sheep::RankUp>,
 ) {
-    for (sheep_transform, sheep_attack) in sheep_q.iter_mut() {
+    for (sheep_entity, mut sheep, sheep_transform, sheep_attack) in sheep_q.iter_mut() {
+        let attack_damage = sheep_attack.attack_damage;
+        if attack_damage <= 0.0 {
+            continue;
+        }
+
         // Calculate the distance between the sheep and the current war machine
         let mut war_machines = war_machines_q
             .iter_mut()
-            .filter(|(_, wm_transform)| {
+            .filter(|(_, _, _, wm_transform)| {
                 sheep_transform
                     .translation
                     .truncate()
@@ -105,7 +159,7 @@ fn sheep_attack(
             })
             .collect::<Vec<_>>();
 
-        war_machines.sort_by(|(_, transform1), (_, transform2)| {
+        war_machines.sort_by(|(_, _, _, transform1), (_, _, _, transform2)| {
             sheep_transform
                 .translation
                 .truncate()
@@ -120,13 +174,36 @@ fn sheep_attack(
         });
 
         // Find the closest war machine
-        if let Some((ref mut wm_health, wm_transform)) = war_machines.get_mut(0) {
+        if let Some((wm_entity, ref mut wm_health, ref mut wm_effects, wm_transform)) =
+            war_machines.get_mut(0)
+        {
             let difference =
                 wm_transform.translation.truncate() - sheep_transform.translation.truncate();
 
             // If the sheep is close enough, sheep_attack it
             if difference.length() <= sheep_attack.attack_range {
-                wm_health.current -= sheep_attack.attack_damage;
+                let was_alive = wm_health.current > 0.0;
+                let taken = wm_effects.damage(wm_health, attack_damage);
+                damage_events.send(DamageEvent {
+                    target: *wm_entity,
+                    amount: taken,
+                    direction: difference,
+                });
+                if let Some(effect) = sheep.on_hit() {
+                    wm_effects.apply(effect);
+                }
+
+                // Hitting a wrecked war machine is no experience, the final blow gets the kill
+                if was_alive {
+                    let mut experience = taken * EXPERIENCE_PER_DAMAGE;
+                    if wm_health.current <= 0.0 {
+                        experience += EXPERIENCE_PER_KILL;
+                        sheep.add_kill();
+                    }
+                    if sheep.gain_experience(experience) {
+                        rank_ups.send(sheep::RankUp(sheep_entity));
+                    }
+                }
             }
         }
     }
@@ -134,11 +211,19 @@ fn sheep_attack(
 
 fn remove_dead_sheep(
     mut commands: Commands,
-    sheep_q: Query<(Entity, &mut Health), (With<sheep::Sheep>, Changed<Health>)>,
+    mut battle_result: ResMut<BattleResult>,
+    sheep_q: Query<(Entity, &sheep::Sheep, &Health, &Transform), Changed<Health>>,
+    mut play_sound: EventWriter<PlaySound>,
 ) {
-    for (sheep, health) in sheep_q.iter() {
+    for (entity, sheep, health, transform) in sheep_q.iter() {
         if health.current <= 0.0 {
-            commands.entity(sheep).despawn_recursive();
+            commands.entity(entity).despawn_recursive();
+            battle_result.sheep_slain += 1;
+            battle_result.fallen_sheep.push(sheep.name().to_string());
+            play_sound.send(PlaySound::at(
+                SoundCue::SheepDeath,
+                transform.translation.truncate(),
+            ));
         }
     }
 }
@@ -164,10 +249,12 @@ fn apply_dying_to_dead_war_machines(
 
 fn setup_ui(mut commands: Commands, ascii_sheet: Res<AsciiSheet>, level: Res<Level>) {
     let lvl_string = level.0;
-    let level_text = write_text(
+    let level_text = write_hud_text(
         &mut commands,
         &ascii_sheet,
-        Vec2::new(6.5, 4.3).extend(50.0),
+        Anchor::TopRight,
+        Vec2::new(-0.5, -0.5),
+        50.0,
         Color::WHITE,
         format!("Lvl: {lvl_string}").as_str(),
     );
@@ -175,6 +262,45 @@ fn setup_ui(mut commands: Commands, ascii_sheet: Res<AsciiSheet>, level: Res<Lev
     commands.entity(level_text).insert(UnloadOnExit);
 }
 
+fn toggle_pause(
+    mut commands: Commands,
+    actions: Actions,
+    ascii_sheet: Res<AsciiSheet>,
+    paused: Option<Res<Paused>>,
+    text_q: Query<Entity, With<PausedText>>,
+) {
+    if !actions.just_released(Action::Pause) {
+        return;
+    }
+
+    match paused {
+        Some(_) => {
+            commands.remove_resource::<Paused>();
+            text_q.for_each(|text| commands.entity(text).despawn_recursive());
+        }
+        None => {
+            commands.insert_resource(Paused);
+            let text = write_hud_text(
+                &mut commands,
+                &ascii_sheet,
+                Anchor::Center,
+                Vec2::ZERO,
+                120.0,
+                Color::WHITE,
+                "PAUSED",
+            );
+            commands
+                .entity(text)
+                .insert(PausedText)
+                .insert(UnloadOnExit);
+        }
+    }
+}
+
+fn unpause(mut commands: Commands) {
+    commands.remove_resource::<Paused>();
+}
+
 /// Increases battle timer and renders it to screen
 fn update_battle_timer(
     mut commands: Commands,
@@ -182,17 +308,27 @@ fn update_battle_timer(
     mut battle_timer: ResMut<BattleTimer>,
     ascii_sheet: Res<AsciiSheet>,
     query: Query<Entity, With<BattleTimerText>>,
+    mut play_sound: EventWriter<PlaySound>,
 ) {
+    let remaining_before = battle_timer.0.duration().as_secs_f32() - battle_timer.0.elapsed_secs();
     battle_timer.0.tick(time.delta());
 
     // Remove old timer
     query.for_each(|timer_text| commands.entity(timer_text).despawn_recursive());
 
     let elapsed = battle_timer.0.duration().as_secs_f32() - battle_timer.0.elapsed_secs();
-    let battle_timer = write_text(
+
+    // Warn on every full second in the last seconds of the battle
+    if elapsed > 0.0 && elapsed <= TIMER_WARNING_SECS && elapsed.ceil() != remaining_before.ceil() {
+        play_sound.send(PlaySound::new(SoundCue::TimerWarning));
+    }
+
+    let battle_timer = write_hud_text(
         &mut commands,
         &ascii_sheet,
-        Vec2::new(-0.5, -4.3).extend(50.0),
+        Anchor::Bottom,
+        Vec2::new(0.0, 0.5),
+        50.0,
         Color::WHITE,
         format!("{elapsed:.2}").as_str(),
     );
@@ -230,14 +366,13 @@ fn check_end_battle(
     }
 }
 
-fn random_position_within_battlefield() -> Transform {
+fn random_position_within_war_machine_zone() -> Transform {
     let mut rng = thread_rng();
     Transform::from_translation(Vec3::new(
-        rng.gen_range(BATTLEFIELD_BOUNDS_X.x..=BATTLEFIELD_BOUNDS_X.y),
+        rng.gen_range(WAR_MACHINE_ZONE_X.x..=WAR_MACHINE_ZONE_X.y),
         rng.gen_range(BATTLEFIELD_BOUNDS_Y.x..=BATTLEFIELD_BOUNDS_Y.y),
         10.0,
     ))
-    .with_scale(Vec3::splat(0.05))
 }
 
 fn setup_battlefield(commands: &mut Commands, asset_server: &Res<AssetServer>) {
@@ -281,127 +416,105 @@ fn setup_battlefield(commands: &mut Commands, asset_server: &Res<AssetServer>) {
     commands.insert_resource(BattleTimer(Timer::from_seconds(DEFAULT_ROUND_TIME, false)));
     commands.insert_resource(BattleResult {
         level_reward_sheep_gained: 10,
+        level_reward_wool_gained: 40,
         ..default()
     });
 }
 
+/// Spawns `count` war machines of every archetype in the war machine zone
+fn spawn_war_machines(
+    commands: &mut Commands,
+    robot_animations: &Res<war_machines::RobotAnimations>,
+    level: &Level,
+    war_machines: &[(Archetype, usize)],
+) {
+    for (archetype, count) in war_machines {
+        for _ in 0..*count {
+            new_war_machine(
+                commands,
+                robot_animations,
+                *archetype,
+                level,
+                random_position_within_war_machine_zone(),
+            );
+        }
+    }
+}
+
 fn setup_level1(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     robot_animations: Res<war_machines::RobotAnimations>,
+    level: Res<Level>,
 ) {
     setup_battlefield(&mut commands, &asset_server);
 
     // Spawn a single war machine
-    let war_machine = new_war_machine(
+    spawn_war_machines(
         &mut commands,
         &robot_animations,
-        random_position_within_battlefield(),
+        &level,
+        &[(Archetype::Eater, 1)],
     );
-    commands
-        .entity(war_machine)
-        .insert(Speed(4.0))
-        .insert(Health::new(60.0))
-        .insert(Attack {
-            attack_damage: 10.0,
-            attack_range: 1.0,
-            spotting_range: 1000.0,
-        })
-        .insert(BehaviourType::ChasingClosest);
 }
 
 fn setup_level2(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     robot_animations: Res<war_machines::RobotAnimations>,
+    level: Res<Level>,
 ) {
     setup_battlefield(&mut commands, &asset_server);
 
-    // Spawn 2 war machines
-    for _ in 0..2 {
-        let war_machine = new_war_machine(
-            &mut commands,
-            &robot_animations,
-            random_position_within_battlefield(),
-        );
-        commands
-            .entity(war_machine)
-            .insert(Speed(5.0))
-            .insert(Health::new(150.0))
-            .insert(Attack {
-                attack_damage: 30.0,
-                attack_range: 1.0,
-                spotting_range: 1000.0,
-            })
-            .insert(BehaviourType::ChasingClosest);
-    }
+    // A bruiser and the scouts going for the wounded sheep
+    spawn_war_machines(
+        &mut commands,
+        &robot_animations,
+        &level,
+        &[(Archetype::Bruiser, 1), (Archetype::Scout, 2)],
+    );
 }
 
 fn setup_level3(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     robot_animations: Res<war_machines::RobotAnimations>,
+    level: Res<Level>,
 ) {
     setup_battlefield(&mut commands, &asset_server);
 
-    // Spawn 5 small war machines
-    for _ in 0..5 {
-        let war_machine = new_war_machine(
-            &mut commands,
-            &robot_animations,
-            random_position_within_battlefield(),
-        );
-        commands
-            .entity(war_machine)
-            .insert(Speed(5.0))
-            .insert(Health::new(80.0))
-            .insert(Attack {
-                attack_damage: 10.0,
-                attack_range: 1.0,
-                spotting_range: 1000.0,
-            })
-            .insert(BehaviourType::ChasingClosest);
-    }
-
-    // Spawn 1 bigger war machine
-    let mut transform = random_position_within_battlefield();
-    transform.scale = Vec3::splat(0.1);
-
-    let war_machine = new_war_machine(&mut commands, &robot_animations, transform);
-    commands
-        .entity(war_machine)
-        .insert(Speed(5.0))
-        .insert(Health::new(300.0))
-        .insert(Attack {
-            attack_damage: 10.0,
-            attack_range: 0.8,
-            spotting_range: 1000.0,
-        })
-        .insert(BehaviourType::ChasingClosest);
+    // Eaters up front, covered by artillery and kept going by a repairer
+    spawn_war_machines(
+        &mut commands,
+        &robot_animations,
+        &level,
+        &[
+            (Archetype::Eater, 3),
+            (Archetype::Bruiser, 1),
+            (Archetype::Artillery, 1),
+            (Archetype::Repairer, 1),
+        ],
+    );
 }
 
 fn setup_level4(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     robot_animations: Res<war_machines::RobotAnimations>,
+    level: Res<Level>,
 ) {
     setup_battlefield(&mut commands, &asset_server);
 
-    // Spawn 3 big war machines
-    for _ in 0..3 {
-        let mut transform = random_position_within_battlefield();
-        transform.scale = Vec3::splat(0.1);
-
-        let war_machine = new_war_machine(&mut commands, &robot_animations, transform);
-        commands
-            .entity(war_machine)
-            .insert(Speed(10.0))
-            .insert(Health::new(300.0))
-            .insert(Attack {
-                attack_damage: 30.0,
-                attack_range: 1.0,
-                spotting_range: 1000.0,
-            })
-            .insert(BehaviourType::ChasingClosest);
-    }
+    // The boss and its escort
+    spawn_war_machines(
+        &mut commands,
+        &robot_animations,
+        &level,
+        &[
+            (Archetype::Boss, 1),
+            (Archetype::Bruiser, 2),
+            (Archetype::Artillery, 1),
+            (Archetype::Repairer, 1),
+        ],
+    );
 }
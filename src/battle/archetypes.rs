@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+
+use super::status::{AreaAttack, Side, StatusEffect};
+use crate::utils::BehaviourType;
+
+/// The kinds of war machines, their stats and looks are in `ARCHETYPES`
+#[derive(Component, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Archetype {
+    /// The regular war machine that eats sheep
+    Eater,
+    /// Fast and weak, it goes for the most wounded sheep
+    Scout,
+    /// Slow, but it takes and deals a lot of damage
+    Bruiser,
+    /// Attacks from afar and backs off from sheep that come too close
+    Artillery,
+    /// Repairs the war machines around it
+    Repairer,
+    /// The biggest and strongest war machine
+    Boss,
+}
+
+impl Archetype {
+    pub const ALL: [Archetype; 6] = [
+        Archetype::Eater,
+        Archetype::Scout,
+        Archetype::Bruiser,
+        Archetype::Artillery,
+        Archetype::Repairer,
+        Archetype::Boss,
+    ];
+
+    pub fn data(&self) -> &'static ArchetypeData {
+        &ARCHETYPES[*self as usize]
+    }
+}
+
+pub struct ArchetypeData {
+    pub name: &'static str,
+    /// Laid out like `EatingRobot.png`, a row each for idling, walking, attacking and dying
+    pub sheet: &'static str,
+    pub walk_frames: usize,
+    /// Scale of the 16x32 sprite
+    pub scale: f32,
+    pub speed: f32,
+    pub health: f32,
+    pub attack_damage: f32,
+    pub attack_range: f32,
+    pub behaviour: BehaviourType,
+    pub repair: Option<Repairer>,
+    /// Done around itself, with the seconds between them
+    pub area_attack: Option<(AreaAttack, f32)>,
+    /// Put on the sheep it hits
+    pub on_hit: Option<StatusEffect>,
+}
+
+/// Indexed by `Archetype`
+const ARCHETYPES: [ArchetypeData; 6] = [
+    ArchetypeData {
+        name: "Eater",
+        sheet: "EatingRobot.png",
+        walk_frames: 7,
+        scale: 0.05,
+        speed: 4.0,
+        health: 60.0,
+        attack_damage: 10.0,
+        attack_range: 1.0,
+        behaviour: BehaviourType::ChasingClosest,
+        repair: None,
+        area_attack: None,
+        on_hit: None,
+    },
+    ArchetypeData {
+        name: "Scout",
+        sheet: "robots/scout.png",
+        walk_frames: 8,
+        scale: 0.05,
+        speed: 7.0,
+        health: 40.0,
+        attack_damage: 5.0,
+        attack_range: 0.8,
+        behaviour: BehaviourType::ChasingWeakest,
+        repair: None,
+        area_attack: None,
+        on_hit: Some(StatusEffect::Poison {
+            per_sec: 2.0,
+            secs: 4.0,
+        }),
+    },
+    ArchetypeData {
+        name: "Bruiser",
+        sheet: "robots/bruiser.png",
+        walk_frames: 8,
+        scale: 0.07,
+        speed: 3.0,
+        health: 250.0,
+        attack_damage: 30.0,
+        attack_range: 1.0,
+        behaviour: BehaviourType::ChasingClosest,
+        repair: None,
+        area_attack: Some((
+            AreaAttack {
+                radius: 1.8,
+                damage: 10.0,
+                effect: Some(StatusEffect::Slow {
+                    factor: 0.5,
+                    secs: 2.0,
+                }),
+                targets: Side::Sheep,
+            },
+            5.0,
+        )),
+        on_hit: None,
+    },
+    ArchetypeData {
+        name: "Artillery",
+        sheet: "robots/artillery.png",
+        walk_frames: 4,
+        scale: 0.05,
+        speed: 3.0,
+        health: 50.0,
+        attack_damage: 15.0,
+        attack_range: 4.0,
+        behaviour: BehaviourType::KeepingDistance,
+        repair: None,
+        area_attack: None,
+        on_hit: Some(StatusEffect::Burn {
+            per_sec: 5.0,
+            secs: 2.0,
+        }),
+    },
+    ArchetypeData {
+        name: "Repairer",
+        sheet: "robots/repairer.png",
+        walk_frames: 8,
+        scale: 0.05,
+        speed: 4.0,
+        health: 70.0,
+        attack_damage: 5.0,
+        attack_range: 2.5,
+        behaviour: BehaviourType::KeepingDistance,
+        repair: Some(Repairer {
+            per_sec: 8.0,
+            range: 3.0,
+        }),
+        area_attack: None,
+        on_hit: None,
+    },
+    ArchetypeData {
+        name: "Boss",
+        sheet: "robots/boss.png",
+        walk_frames: 8,
+        scale: 0.1,
+        speed: 4.0,
+        health: 600.0,
+        attack_damage: 40.0,
+        attack_range: 1.2,
+        behaviour: BehaviourType::ChasingClosest,
+        repair: None,
+        area_attack: None,
+        on_hit: None,
+    },
+];
+
+/// Repairers heal the other war machines within `range` by `per_sec` health every second
+#[derive(Component, Copy, Clone)]
+pub struct Repairer {
+    pub per_sec: f32,
+    pub range: f32,
+}
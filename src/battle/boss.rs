@@ -0,0 +1,439 @@
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use iyes_loopless::prelude::*;
+use rand::{thread_rng, Rng};
+
+use super::archetypes::Archetype;
+use super::damage::DamageEvent;
+use super::states::{Casting, Dying};
+use super::status::{AreaAttack, AreaAttackEvent, Side, StatusEffect, StatusEffects};
+use super::war_machines::{new_war_machine, RobotAnimations, WarMachine};
+use super::{battle_running, Level};
+use crate::audio::{PlaySound, SoundCue};
+use crate::sheep::Sheep;
+use crate::ui::{write_hud_text, Anchor, AsciiSheet, HudAnchor};
+use crate::utils::{Health, UnloadOnExit};
+use crate::GameState;
+
+const FIRST_ATTACK_SECS: f32 = 3.0;
+const ATTACK_COOLDOWN_SECS: f32 = 4.0;
+/// How long the player sees an attack coming
+const TELEGRAPH_SECS: f32 = 1.0;
+/// Sheep this close to a charging boss are run over
+const CHARGE_HIT_RADIUS: f32 = 1.0;
+const TELEGRAPH_Z: f32 = 5.0;
+
+const BOSS_BAR_WIDTH: f32 = 16.0;
+const BOSS_BAR_HEIGHT: f32 = 0.4;
+
+#[derive(Copy, Clone)]
+enum BossAttack {
+    /// Hits every sheep around the boss
+    Stomp(AreaAttack),
+    /// Rushes towards the closest sheep, running over every sheep on the way
+    Charge { speed: f32, secs: f32, damage: f32 },
+    /// Calls in war machines to help
+    Summon { archetype: Archetype, count: usize },
+}
+
+/// The boss learns a new attack once its health drops below `health_below`
+struct BossPhase {
+    health_below: f32,
+    attack: BossAttack,
+}
+
+const PHASES: [BossPhase; 3] = [
+    BossPhase {
+        health_below: 1.0,
+        attack: BossAttack::Stomp(AreaAttack {
+            radius: 2.5,
+            damage: 15.0,
+            effect: Some(StatusEffect::Stun { secs: 1.0 }),
+            targets: Side::Sheep,
+        }),
+    },
+    BossPhase {
+        health_below: 0.66,
+        attack: BossAttack::Charge {
+            speed: 12.0,
+            secs: 0.5,
+            damage: 20.0,
+        },
+    },
+    BossPhase {
+        health_below: 0.33,
+        attack: BossAttack::Summon {
+            archetype: Archetype::Scout,
+            count: 2,
+        },
+    },
+];
+
+/// The attack the boss is telegraphing or doing right now
+struct BossAction {
+    attack: BossAttack,
+    telegraph: Timer,
+    telegraph_entity: Entity,
+    /// Towards the closest sheep, when the attack started
+    direction: Vec2,
+    /// Running once the charge started
+    charge: Option<Timer>,
+    /// Sheep already run over by the charge
+    hit: Vec<Entity>,
+}
+
+/// War machines with this component go through the `PHASES` and use their attacks
+#[derive(Component)]
+pub struct Boss {
+    phase: usize,
+    next_attack: usize,
+    cooldown: Timer,
+    action: Option<BossAction>,
+}
+
+impl Default for Boss {
+    fn default() -> Self {
+        Self {
+            phase: 0,
+            next_attack: 0,
+            cooldown: Timer::from_seconds(FIRST_ATTACK_SECS, false),
+            action: None,
+        }
+    }
+}
+
+/// The area an attack of the boss is about to hit
+#[derive(Component)]
+struct Telegraph;
+
+#[derive(Component)]
+struct BossBarFill;
+
+#[derive(Component)]
+struct BossBarText;
+
+struct BossGraphics {
+    circle: Mesh2dHandle,
+    square: Mesh2dHandle,
+    danger: Handle<ColorMaterial>,
+    summon: Handle<ColorMaterial>,
+}
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system_to_stage(StartupStage::PreStartup, load_graphics)
+            .add_enter_system(GameState::Battle, spawn_boss_bar)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_if(battle_running)
+                    .with_system(update_boss_phases)
+                    .with_system(start_boss_attacks)
+                    .with_system(boss_attacks)
+                    .with_system(cancel_attacks_of_dying_bosses)
+                    .with_system(update_boss_bar)
+                    .into(),
+            );
+    }
+}
+
+fn load_graphics(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(BossGraphics {
+        circle: meshes.add(shape::Circle::new(1.0).into()).into(),
+        square: meshes.add(shape::Quad::new(Vec2::ONE).into()).into(),
+        danger: materials.add(ColorMaterial::from(Color::rgba(1.0, 0.1, 0.1, 0.35))),
+        summon: materials.add(ColorMaterial::from(Color::rgba(0.6, 0.2, 1.0, 0.35))),
+    });
+}
+
+fn update_boss_phases(
+    mut boss_q: Query<(&mut Boss, &Health, &Transform), Without<Dying>>,
+    mut play_sound: EventWriter<PlaySound>,
+) {
+    for (mut boss, health, transform) in boss_q.iter_mut() {
+        let phase = PHASES
+            .iter()
+            .rposition(|phase| health.ratio() <= phase.health_below)
+            .unwrap_or(0);
+
+        // The attack of the new phase comes first
+        if phase > boss.phase {
+            boss.phase = phase;
+            boss.next_attack = phase;
+            play_sound.send(PlaySound::at(
+                SoundCue::WarMachineSpotted,
+                transform.translation.truncate(),
+            ));
+        }
+    }
+}
+
+/// Picks the next attack once the cooldown is over and shows where it is going to hit
+fn start_boss_attacks(
+    mut commands: Commands,
+    time: Res<Time>,
+    graphics: Res<BossGraphics>,
+    mut boss_q: Query<
+        (Entity, &mut Boss, &Transform, &StatusEffects),
+        (Without<Dying>, Without<Casting>),
+    >,
+    sheep_q: Query<&Transform, With<Sheep>>,
+) {
+    for (entity, mut boss, transform, effects) in boss_q.iter_mut() {
+        if effects.is_stunned() || !boss.cooldown.tick(time.delta()).finished() {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let closest = sheep_q
+            .iter()
+            .map(|sheep_transform| sheep_transform.translation.truncate())
+            .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+        let target = match closest {
+            Some(target) => target,
+            None => continue,
+        };
+
+        let attack = PHASES[boss.next_attack % (boss.phase + 1)].attack;
+        boss.next_attack += 1;
+
+        let (mesh, material) = match attack {
+            BossAttack::Stomp { .. } => (graphics.circle.clone(), graphics.danger.clone()),
+            BossAttack::Charge { .. } => (graphics.square.clone(), graphics.danger.clone()),
+            BossAttack::Summon { .. } => (graphics.circle.clone(), graphics.summon.clone()),
+        };
+        let telegraph = commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh,
+                material,
+                transform: Transform::from_translation(position.extend(TELEGRAPH_Z))
+                    .with_scale(Vec3::ZERO),
+                ..default()
+            })
+            .insert(Telegraph)
+            .insert(UnloadOnExit)
+            .insert(Name::from("BossTelegraph"))
+            .id();
+
+        boss.action = Some(BossAction {
+            attack,
+            telegraph: Timer::from_seconds(TELEGRAPH_SECS, false),
+            telegraph_entity: telegraph,
+            direction: (target - position).normalize_or_zero(),
+            charge: None,
+            hit: Vec::new(),
+        });
+        commands.entity(entity).insert(Casting);
+    }
+}
+
+/// Grows the telegraph until the attack hits, then does the attack
+#[allow(clippy::too_many_arguments)]
+fn boss_attacks(
+    mut commands: Commands,
+    time: Res<Time>,
+    robot_animations: Res<RobotAnimations>,
+    level: Res<Level>,
+    mut boss_q: Query<(Entity, &mut Boss, &mut Transform), (With<WarMachine>, With<Casting>)>,
+    mut sheep_q: Query<
+        (Entity, &Transform, &mut Health, &mut StatusEffects),
+        (With<Sheep>, Without<WarMachine>),
+    >,
+    mut telegraph_q: Query<&mut Transform, (With<Telegraph>, Without<WarMachine>, Without<Sheep>)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut area_attacks: EventWriter<AreaAttackEvent>,
+) {
+    for (entity, mut boss, mut transform) in boss_q.iter_mut() {
+        let action = match boss.action.as_mut() {
+            Some(action) => action,
+            None => continue,
+        };
+        let position = transform.translation.truncate();
+
+        if !action.telegraph.finished() {
+            let progress = action.telegraph.tick(time.delta()).percent();
+            if let Ok(mut telegraph) = telegraph_q.get_mut(action.telegraph_entity) {
+                *telegraph = telegraph_transform(action, position, progress);
+            }
+            if !action.telegraph.finished() {
+                continue;
+            }
+        }
+
+        let done = match action.attack {
+            BossAttack::Stomp(stomp) => {
+                area_attacks.send(AreaAttackEvent {
+                    center: position,
+                    attack: stomp,
+                });
+                true
+            }
+            BossAttack::Charge {
+                speed,
+                secs,
+                damage,
+            } => {
+                let charge = action
+                    .charge
+                    .get_or_insert_with(|| Timer::from_seconds(secs, false));
+                charge.tick(time.delta());
+                transform.translation +=
+                    action.direction.extend(0.0) * speed * time.delta_seconds();
+                let position = transform.translation.truncate();
+
+                for (sheep, sheep_transform, mut health, mut effects) in sheep_q.iter_mut() {
+                    let difference = sheep_transform.translation.truncate() - position;
+                    if difference.length() <= CHARGE_HIT_RADIUS && !action.hit.contains(&sheep) {
+                        action.hit.push(sheep);
+                        let taken = effects.damage(&mut health, damage);
+                        if taken > 0.0 {
+                            damage_events.send(DamageEvent {
+                                target: sheep,
+                                amount: taken,
+                                direction: action.direction,
+                            });
+                        }
+                    }
+                }
+                charge.finished()
+            }
+            BossAttack::Summon { archetype, count } => {
+                let mut rng = thread_rng();
+                for _ in 0..count {
+                    let offset = Vec2::new(rng.gen_range(-1.5..=1.5), rng.gen_range(-1.5..=1.5));
+                    new_war_machine(
+                        &mut commands,
+                        &robot_animations,
+                        archetype,
+                        &level,
+                        Transform::from_translation((position + offset).extend(10.0)),
+                    );
+                }
+                true
+            }
+        };
+
+        if done {
+            commands.entity(action.telegraph_entity).despawn_recursive();
+            commands.entity(entity).remove::<Casting>();
+            boss.action = None;
+            boss.cooldown = Timer::from_seconds(ATTACK_COOLDOWN_SECS, false);
+        }
+    }
+}
+
+/// The stomp and summon circles grow around the boss, the charge shows its path
+fn telegraph_transform(action: &BossAction, position: Vec2, progress: f32) -> Transform {
+    match action.attack {
+        BossAttack::Stomp(stomp) => Transform::from_translation(position.extend(TELEGRAPH_Z))
+            .with_scale(Vec3::new(stomp.radius, stomp.radius, 1.0) * progress),
+        BossAttack::Charge { speed, secs, .. } => {
+            let length = speed * secs * progress;
+            Transform {
+                translation: (position + action.direction * length / 2.0).extend(TELEGRAPH_Z),
+                rotation: Quat::from_rotation_z(action.direction.y.atan2(action.direction.x)),
+                scale: Vec3::new(length, CHARGE_HIT_RADIUS * 2.0, 1.0),
+            }
+        }
+        BossAttack::Summon { .. } => Transform::from_translation(position.extend(TELEGRAPH_Z))
+            .with_scale(Vec3::new(1.5, 1.5, 1.0) * progress),
+    }
+}
+
+fn cancel_attacks_of_dying_bosses(
+    mut commands: Commands,
+    mut boss_q: Query<(Entity, &mut Boss), Added<Dying>>,
+) {
+    for (entity, mut boss) in boss_q.iter_mut() {
+        if let Some(action) = boss.action.take() {
+            commands.entity(action.telegraph_entity).despawn_recursive();
+        }
+        commands.entity(entity).remove::<Casting>();
+    }
+}
+
+/// Bosses get a big health bar at the top of the screen instead of the small one
+fn spawn_boss_bar(mut commands: Commands, boss_q: Query<(), With<Boss>>) {
+    if boss_q.is_empty() {
+        return;
+    }
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::BLACK,
+                custom_size: Some(Vec2::new(BOSS_BAR_WIDTH, BOSS_BAR_HEIGHT) + Vec2::splat(0.2)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudAnchor::new(Anchor::Top, Vec2::new(0.0, -1.8), 50.0))
+        .insert(UnloadOnExit)
+        .insert(Name::from("BossBarBackground"));
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::RED,
+                custom_size: Some(Vec2::new(BOSS_BAR_WIDTH, BOSS_BAR_HEIGHT)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudAnchor::new(Anchor::Top, Vec2::new(0.0, -1.8), 51.0))
+        .insert(BossBarFill)
+        .insert(UnloadOnExit)
+        .insert(Name::from("BossBar"));
+}
+
+/// Shows the health of all bosses together, and the phase they are in
+fn update_boss_bar(
+    mut commands: Commands,
+    ascii_sheet: Res<AsciiSheet>,
+    boss_q: Query<(&Boss, &Health)>,
+    mut fill_q: Query<(&mut Sprite, &mut HudAnchor), With<BossBarFill>>,
+    text_q: Query<Entity, With<BossBarText>>,
+    mut shown: Local<Option<usize>>,
+) {
+    let (current, max) = boss_q
+        .iter()
+        .fold((0.0, 0.0), |(current, max), (_, health)| {
+            (current + health.current.max(0.0), max + health.max)
+        });
+    let ratio = if max > 0.0 { current / max } else { 0.0 };
+
+    for (mut sprite, mut anchor) in fill_q.iter_mut() {
+        sprite.custom_size = Some(Vec2::new(BOSS_BAR_WIDTH * ratio, BOSS_BAR_HEIGHT));
+        anchor.offset.x = -BOSS_BAR_WIDTH * (1.0 - ratio) / 2.0;
+    }
+
+    let phase = match boss_q.iter().map(|(boss, _)| boss.phase).max() {
+        Some(phase) if !fill_q.is_empty() => phase,
+        _ => return,
+    };
+    if *shown == Some(phase) && !text_q.is_empty() {
+        return;
+    }
+    *shown = Some(phase);
+
+    text_q.for_each(|text| commands.entity(text).despawn_recursive());
+
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Top,
+        Vec2::new(0.0, -0.5),
+        50.0,
+        Color::RED,
+        format!("BOSS  phase {}/{}", phase + 1, PHASES.len()).as_str(),
+    );
+    commands
+        .entity(text)
+        .insert(BossBarText)
+        .insert(UnloadOnExit);
+}
@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use super::battle_running;
+use crate::audio::{PlaySound, SoundCue};
+use crate::sheep::Sheep;
+use crate::ui::{write_text, AsciiSheet, LETTER_TILE_WIDTH};
+use crate::utils::UnloadOnExit;
+use crate::GameState;
+
+const HIT_FLASH_SECS: f32 = 0.2;
+const HIT_FLASH_BLINK_SECS_INV: f32 = 30.0;
+const HIT_FLASH_COLOR: Color = Color::rgb(1.0, 0.25, 0.25);
+
+const KNOCKBACK_PER_DAMAGE: f32 = 0.4;
+const MAX_KNOCKBACK_SPEED: f32 = 8.0;
+const KNOCKBACK_DAMPING: f32 = 12.0;
+
+// Sheep deal damage every frame, so the numbers are accumulated and shown in batches
+const DAMAGE_NUMBER_INTERVAL_SECS: f32 = 0.3;
+const DAMAGE_NUMBER_LIFETIME_SECS: f32 = 0.8;
+const DAMAGE_NUMBER_RISE_SPEED: f32 = 1.5;
+
+/// Sent whenever an entity's `Health` is reduced in battle
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    /// Direction in which the target is pushed back, usually away from the attacker
+    pub direction: Vec2,
+}
+
+/// Keeps the hit feedback state of an entity that can take damage. Attach it on spawn, so the
+/// feedback systems never have to insert components on entities that might die this frame.
+#[derive(Component)]
+pub struct HitFeedback {
+    flash: Option<Timer>,
+    /// Colours of the entity and its flashing children before the hit
+    base_colors: Vec<(Entity, Color)>,
+    knockback: Vec2,
+    pending_damage: f32,
+    number_timer: Timer,
+}
+
+impl Default for HitFeedback {
+    fn default() -> Self {
+        Self {
+            flash: None,
+            base_colors: Vec::new(),
+            knockback: Vec2::ZERO,
+            pending_damage: 0.0,
+            number_timer: Timer::from_seconds(DAMAGE_NUMBER_INTERVAL_SECS, true),
+        }
+    }
+}
+
+/// Children with this component flash together with their parent, like the head of a sheep
+#[derive(Component)]
+pub struct FlashWithParent;
+
+/// Floating damage number, rises up and despawns after its timer runs out
+#[derive(Component)]
+pub struct DamageNumber {
+    timer: Timer,
+}
+
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_system_set(
+                ConditionSet::new()
+                    .run_if(battle_running)
+                    .label("update")
+                    .with_system(receive_damage)
+                    .with_system(flash_hit_sprites)
+                    .with_system(apply_knockback)
+                    .with_system(spawn_damage_numbers)
+                    .with_system(float_damage_numbers)
+                    .into(),
+            )
+            .add_exit_system(GameState::Battle, reset_hit_feedback);
+    }
+}
+
+fn receive_damage(
+    mut events: EventReader<DamageEvent>,
+    mut q: Query<(&mut HitFeedback, &Transform, Option<&Sheep>)>,
+    mut play_sound: EventWriter<PlaySound>,
+) {
+    for event in events.iter() {
+        if let Ok((mut feedback, transform, sheep)) = q.get_mut(event.target) {
+            if sheep.is_some() {
+                play_sound.send(PlaySound::at(
+                    SoundCue::SheepHit,
+                    transform.translation.truncate(),
+                ));
+            }
+
+            feedback.flash = Some(Timer::from_seconds(HIT_FLASH_SECS, false));
+
+            let knockback = feedback.knockback
+                + event.direction.normalize_or_zero() * event.amount * KNOCKBACK_PER_DAMAGE;
+            feedback.knockback = knockback.clamp_length_max(MAX_KNOCKBACK_SPEED);
+
+            feedback.pending_damage += event.amount;
+        }
+    }
+}
+
+/// Blinks the sprites while the entity was recently hit and restores their colours afterwards
+fn flash_hit_sprites(
+    time: Res<Time>,
+    mut q: Query<(Entity, &mut HitFeedback, Option<&Children>)>,
+    flash_children_q: Query<(), With<FlashWithParent>>,
+    mut sprite_q: Query<&mut TextureAtlasSprite>,
+) {
+    for (entity, mut feedback, children) in q.iter_mut() {
+        let finished = match feedback.flash.as_mut() {
+            Some(timer) => timer.tick(time.delta()).finished(),
+            None => continue,
+        };
+
+        if feedback.base_colors.is_empty() {
+            let flashing = std::iter::once(entity).chain(
+                children
+                    .map(|children| children.iter())
+                    .into_iter()
+                    .flatten()
+                    .copied()
+                    .filter(|child| flash_children_q.contains(*child)),
+            );
+            feedback.base_colors = flashing
+                .filter_map(|flashed| Some((flashed, sprite_q.get(flashed).ok()?.color)))
+                .collect();
+        }
+
+        let blink = (time.seconds_since_startup() as f32 * HIT_FLASH_BLINK_SECS_INV).sin() >= 0.0;
+        for (flashed, base_color) in feedback.base_colors.iter() {
+            if let Ok(mut sprite) = sprite_q.get_mut(*flashed) {
+                sprite.color = if blink && !finished {
+                    HIT_FLASH_COLOR
+                } else {
+                    *base_color
+                };
+            }
+        }
+
+        if finished {
+            feedback.base_colors.clear();
+            feedback.flash = None;
+        }
+    }
+}
+
+/// Pushes the entity back, `bounds_check` keeps it within its `Bounds`
+fn apply_knockback(time: Res<Time>, mut q: Query<(&mut HitFeedback, &mut Transform)>) {
+    for (mut feedback, mut transform) in q.iter_mut() {
+        if feedback.knockback == Vec2::ZERO {
+            continue;
+        }
+
+        transform.translation += feedback.knockback.extend(0.0) * time.delta_seconds();
+
+        feedback.knockback *= (-KNOCKBACK_DAMPING * time.delta_seconds()).exp();
+        if feedback.knockback.length_squared() < 0.01 {
+            feedback.knockback = Vec2::ZERO;
+        }
+    }
+}
+
+fn spawn_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    ascii_sheet: Res<AsciiSheet>,
+    mut q: Query<(&mut HitFeedback, &Transform, Option<&Sheep>)>,
+) {
+    for (mut feedback, transform, sheep) in q.iter_mut() {
+        feedback.number_timer.tick(time.delta());
+
+        if !feedback.number_timer.just_finished() || feedback.pending_damage <= 0.0 {
+            continue;
+        }
+
+        let text = if feedback.pending_damage >= 1.0 {
+            format!("{:.0}", feedback.pending_damage)
+        } else {
+            format!("{:.1}", feedback.pending_damage)
+        };
+        feedback.pending_damage = 0.0;
+
+        // Sheep losing health is bad news, war machines losing health is good news
+        let color = match sheep {
+            Some(_) => Color::ORANGE_RED,
+            None => Color::YELLOW,
+        };
+
+        let width = text.chars().count() as f32 * LETTER_TILE_WIDTH / 16.0;
+        let number = write_text(&mut commands, &ascii_sheet, Vec3::ZERO, color, &text);
+        commands
+            .entity(number)
+            .insert(Transform::from_translation(
+                (transform.translation.truncate() + Vec2::new(-width / 2.0, 0.8)).extend(60.0),
+            ))
+            .insert(DamageNumber {
+                timer: Timer::from_seconds(DAMAGE_NUMBER_LIFETIME_SECS, false),
+            })
+            .insert(UnloadOnExit);
+    }
+}
+
+/// Surviving sheep go back to the pen, so make sure none of them stay tinted or pushed around
+fn reset_hit_feedback(
+    mut q: Query<&mut HitFeedback>,
+    mut sprite_q: Query<&mut TextureAtlasSprite>,
+) {
+    for mut feedback in q.iter_mut() {
+        for (flashed, base_color) in feedback.base_colors.iter() {
+            if let Ok(mut sprite) = sprite_q.get_mut(*flashed) {
+                sprite.color = *base_color;
+            }
+        }
+        *feedback = HitFeedback::default();
+    }
+}
+
+fn float_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q: Query<(Entity, &mut DamageNumber, &mut Transform)>,
+) {
+    for (entity, mut number, mut transform) in q.iter_mut() {
+        number.timer.tick(time.delta());
+
+        if number.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        transform.translation.y += DAMAGE_NUMBER_RISE_SPEED * time.delta_seconds();
+    }
+}
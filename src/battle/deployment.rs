@@ -0,0 +1,329 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use super::{Level, BATTLEFIELD_BOUNDS_X, BATTLEFIELD_BOUNDS_Y};
+use crate::actions::{Action, Actions, InputMap};
+use crate::dialog::no_modal_open;
+use crate::drag::Drag;
+use crate::pen::PenUpgrades;
+use crate::sheep::{spawn_wounded_sheep, Sheep, SheepParent, SheepSprites};
+use crate::ui::{write_hud_text, Anchor, AsciiSheet};
+use crate::utils::{bounds_check, despawn_entities_with_component, Bounds, Health};
+use crate::{GameState, NewGame};
+
+/// Sheep left on the bench, on the far left, stay in the pen during the battle
+pub const BENCH_ZONE_X: Vec2 = Vec2::new(BATTLEFIELD_BOUNDS_X.x, -3.6);
+/// The squad is placed between the bench and the war machines
+pub const SHEEP_DEPLOY_ZONE_X: Vec2 = Vec2::new(-3.0, 1.0);
+/// The war machines spawn on the right side of the battlefield
+pub const WAR_MACHINE_ZONE_X: Vec2 = Vec2::new(2.5, BATTLEFIELD_BOUNDS_X.y);
+
+/// How many sheep can go into a battle at most
+pub const MAX_SQUAD_SIZE: usize = 8;
+
+/// Sum of the levels of the squad can't go over this, so strong sheep take more of the squad
+pub fn squad_level_cap(level: &Level) -> usize {
+    6 + 4 * level.0
+}
+
+/// The sheep sitting out the battle, with where they sat and how much of their health was left.
+/// They are taken off the battlefield and come back once the player returns to the pen.
+#[derive(Default)]
+struct Bench(Vec<(Sheep, Transform, f32)>);
+
+/// Marker component for everything that is only shown while deploying
+#[derive(Component)]
+struct DeploymentEntity;
+
+/// Marker component for the squad size text
+#[derive(Component)]
+struct SquadText;
+
+pub struct DeploymentPlugin;
+
+impl Plugin for DeploymentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system_set(
+            GameState::Deployment,
+            ConditionSet::new()
+                .with_system(deploy_sheep)
+                .with_system(setup_deployment_ui)
+                .into(),
+        )
+        .add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::Deployment)
+                .with_system(assign_dropped_sheep)
+                .with_system(update_squad_text)
+                .with_system(start_battle.run_if(no_modal_open))
+                .into(),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            bounds_check.run_in_state(GameState::Deployment),
+        )
+        .add_exit_system(
+            GameState::Deployment,
+            despawn_entities_with_component::<DeploymentEntity>,
+        )
+        .add_enter_system(GameState::Battle, release_sheep)
+        .add_enter_system(GameState::Herding, return_benched_sheep);
+    }
+}
+
+fn is_benched(transform: &Transform) -> bool {
+    transform.translation.x < (BENCH_ZONE_X.y + SHEEP_DEPLOY_ZONE_X.x) / 2.0
+}
+
+/// Maps `relative_x` from `0.0` to `1.0` into the `zone`
+fn position_in_zone(zone: Vec2, relative_x: f32) -> f32 {
+    zone.x + relative_x.clamp(0.0, 1.0) * (zone.y - zone.x)
+}
+
+/// Number of sheep and the sum of their levels in the squad
+fn squad_size<'a>(sheep: impl Iterator<Item = (&'a Sheep, &'a Transform)>) -> (usize, usize) {
+    sheep
+        .filter(|(_, transform)| !is_benched(transform))
+        .fold((0, 0), |(count, levels), (sheep, _)| {
+            (count + 1, levels + sheep.sum_levels() as usize)
+        })
+}
+
+/// Picks the strongest sheep that fit into the squad and moves them into the deployment zone, the
+/// rest go to the bench. Both keep their formation from the pen.
+fn deploy_sheep(level: Res<Level>, mut sheep_q: Query<(&Sheep, &mut Transform, &mut Bounds)>) {
+    let battlefield_width = BATTLEFIELD_BOUNDS_X.y - BATTLEFIELD_BOUNDS_X.x;
+    let level_cap = squad_level_cap(&level);
+
+    let mut sheep = sheep_q.iter_mut().collect::<Vec<_>>();
+    sheep.sort_by(|(sheep1, _, _), (sheep2, _, _)| {
+        sheep2
+            .sum_levels()
+            .partial_cmp(&sheep1.sum_levels())
+            .unwrap()
+    });
+
+    let (mut count, mut levels) = (0, 0);
+    for (sheep, transform, bounds) in sheep.iter_mut() {
+        let sheep_levels = sheep.sum_levels() as usize;
+        let zone = if count < MAX_SQUAD_SIZE && levels + sheep_levels <= level_cap {
+            count += 1;
+            levels += sheep_levels;
+            SHEEP_DEPLOY_ZONE_X
+        } else {
+            BENCH_ZONE_X
+        };
+
+        let relative_x = (transform.translation.x - BATTLEFIELD_BOUNDS_X.x) / battlefield_width;
+        transform.translation.x = position_in_zone(zone, relative_x);
+
+        **bounds = Bounds {
+            x: (BENCH_ZONE_X.x, SHEEP_DEPLOY_ZONE_X.y),
+            y: (BATTLEFIELD_BOUNDS_Y.x, BATTLEFIELD_BOUNDS_Y.y),
+        };
+    }
+}
+
+/// Dropped sheep snap into the bench or the deployment zone. If the squad is full, they go
+/// back to the bench.
+fn assign_dropped_sheep(
+    level: Res<Level>,
+    dropped: RemovedComponents<Drag>,
+    mut sheep_q: Query<(Entity, &Sheep, &mut Transform)>,
+) {
+    for dropped in dropped.iter() {
+        let (count, levels) = squad_size(
+            sheep_q
+                .iter()
+                .filter(|(entity, _, _)| *entity != dropped)
+                .map(|(_, sheep, transform)| (sheep, transform)),
+        );
+
+        if let Ok((_, sheep, mut transform)) = sheep_q.get_mut(dropped) {
+            let fits = count < MAX_SQUAD_SIZE
+                && levels + sheep.sum_levels() as usize <= squad_level_cap(&level);
+
+            let x = transform.translation.x;
+            transform.translation.x = if is_benched(&transform) || !fits {
+                x.clamp(BENCH_ZONE_X.x, BENCH_ZONE_X.y)
+            } else {
+                x.clamp(SHEEP_DEPLOY_ZONE_X.x, SHEEP_DEPLOY_ZONE_X.y)
+            };
+        }
+    }
+}
+
+/// Once the battle starts, the squad can go anywhere on the battlefield
+fn release_sheep(mut sheep_q: Query<&mut Bounds, With<Sheep>>) {
+    for mut bounds in sheep_q.iter_mut() {
+        *bounds = Bounds {
+            x: (BATTLEFIELD_BOUNDS_X.x, BATTLEFIELD_BOUNDS_X.y),
+            y: (BATTLEFIELD_BOUNDS_Y.x, BATTLEFIELD_BOUNDS_Y.y),
+        };
+    }
+}
+
+/// The benched sheep join the others in the pen, unless the game is over
+fn return_benched_sheep(
+    mut commands: Commands,
+    texture: Res<SheepSprites>,
+    upgrades: Res<PenUpgrades>,
+    bench: Option<Res<Bench>>,
+    new_game: Option<Res<NewGame>>,
+    sheep_parent: Query<Entity, With<SheepParent>>,
+) {
+    let bench = match bench {
+        Some(bench) => bench,
+        None => return,
+    };
+
+    if new_game.is_none() {
+        let sheep = bench
+            .0
+            .iter()
+            .map(|(sheep, transform, health_ratio)| {
+                spawn_wounded_sheep(
+                    &mut commands,
+                    &texture,
+                    *transform,
+                    upgrades.pen_bounds(),
+                    sheep.clone(),
+                    *health_ratio,
+                )
+            })
+            .collect::<Vec<_>>();
+        commands.entity(sheep_parent.single()).push_children(&sheep);
+    }
+
+    commands.remove_resource::<Bench>();
+}
+
+fn spawn_zone(commands: &mut Commands, zone_x: Vec2, color: Color, name: &str) {
+    let size = Vec2::new(
+        zone_x.y - zone_x.x,
+        BATTLEFIELD_BOUNDS_Y.y - BATTLEFIELD_BOUNDS_Y.x,
+    );
+    let center = Vec2::new(
+        (zone_x.x + zone_x.y) / 2.0,
+        (BATTLEFIELD_BOUNDS_Y.x + BATTLEFIELD_BOUNDS_Y.y) / 2.0,
+    );
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(size),
+                ..default()
+            },
+            transform: Transform::from_translation(center.extend(1.0)),
+            ..default()
+        })
+        .insert(DeploymentEntity)
+        .insert(Name::from(name));
+}
+
+fn setup_deployment_ui(
+    mut commands: Commands,
+    ascii_sheet: Res<AsciiSheet>,
+    input_map: Res<InputMap>,
+) {
+    spawn_zone(
+        &mut commands,
+        BENCH_ZONE_X,
+        Color::rgba(0.6, 0.6, 0.6, 0.25),
+        "BenchZone",
+    );
+    spawn_zone(
+        &mut commands,
+        SHEEP_DEPLOY_ZONE_X,
+        Color::rgba(0.3, 1.0, 0.3, 0.25),
+        "SheepDeployZone",
+    );
+    spawn_zone(
+        &mut commands,
+        WAR_MACHINE_ZONE_X,
+        Color::rgba(1.0, 0.2, 0.2, 0.25),
+        "WarMachineZone",
+    );
+
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Bottom,
+        Vec2::new(0.0, 0.5),
+        50.0,
+        Color::WHITE,
+        format!(
+            "Pick your squad, press {} to fight!",
+            input_map.label(Action::StartBattle)
+        )
+        .as_str(),
+    );
+    commands.entity(text).insert(DeploymentEntity);
+}
+
+fn update_squad_text(
+    mut commands: Commands,
+    level: Res<Level>,
+    ascii_sheet: Res<AsciiSheet>,
+    sheep_q: Query<(&Sheep, &Transform)>,
+    text_q: Query<Entity, With<SquadText>>,
+    mut shown: Local<Option<(usize, usize)>>,
+) {
+    let size = squad_size(sheep_q.iter());
+    if *shown == Some(size) && !text_q.is_empty() {
+        return;
+    }
+    *shown = Some(size);
+
+    text_q.for_each(|text| commands.entity(text).despawn_recursive());
+
+    let (count, levels) = size;
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Top,
+        Vec2::new(0.0, -0.5),
+        50.0,
+        Color::WHITE,
+        format!(
+            "Squad: {count}/{MAX_SQUAD_SIZE} sheep, lvl {levels}/{}",
+            squad_level_cap(&level)
+        )
+        .as_str(),
+    );
+    commands
+        .entity(text)
+        .insert(SquadText)
+        .insert(DeploymentEntity);
+}
+
+/// Starts the battle with the squad, the benched sheep wait on the `Bench` until it is over
+fn start_battle(
+    mut commands: Commands,
+    actions: Actions,
+    sheep_q: Query<(Entity, &Sheep, &Transform, &Health)>,
+) {
+    if !actions.just_released(Action::StartBattle) {
+        return;
+    }
+
+    // Without any sheep the battle would be lost right away
+    if sheep_q
+        .iter()
+        .all(|(_, _, transform, _)| is_benched(transform))
+    {
+        return;
+    }
+
+    let mut bench = Bench::default();
+    for (entity, sheep, transform, health) in sheep_q.iter() {
+        if is_benched(transform) {
+            bench.0.push((sheep.clone(), *transform, health.ratio()));
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    commands.insert_resource(bench);
+
+    commands.insert_resource(NextState(GameState::Battle));
+}
@@ -14,13 +14,22 @@ impl Walking {
     pub const ANIMATION: &'static str = "walking";
 }
 
-#[derive(Component, Default)]
+/// Bites the `target` it walked up to
+#[derive(Component)]
 pub struct Attacking {
+    pub target: Entity,
     pub has_started: bool,
 }
 
 impl Attacking {
     pub const ANIMATION: &'static str = "attacking";
+
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            has_started: false,
+        }
+    }
 }
 
 #[derive(Component, Default)]
@@ -31,3 +40,7 @@ pub struct Dying {
 impl Dying {
     pub const ANIMATION: &'static str = "dying";
 }
+
+/// While a war machine is busy with a special attack, it doesn't idle, walk or attack
+#[derive(Component)]
+pub struct Casting;
@@ -0,0 +1,384 @@
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use iyes_loopless::prelude::*;
+
+use super::battle_running;
+use super::damage::DamageEvent;
+use super::states::Dying;
+use super::war_machines::WarMachine;
+use crate::sheep::Sheep;
+use crate::stats::{Modifier, ModifierSource, Stat, Stats};
+use crate::utils::{Health, UnloadOnExit};
+use crate::GameState;
+
+const AREA_FLASH_SECS: f32 = 0.3;
+const AREA_FLASH_Z: f32 = 4.0;
+
+/// Effects that last for `secs`, see `StatusEffects::apply`
+#[derive(Copy, Clone)]
+pub enum StatusEffect {
+    /// Can't move or attack
+    Stun { secs: f32 },
+    /// Moves and attacks at `factor` of the usual speed
+    Slow { factor: f32, secs: f32 },
+    /// Loses `per_sec` health every second
+    Burn { per_sec: f32, secs: f32 },
+    /// Loses `per_sec` health every second and can't be repaired
+    Poison { per_sec: f32, secs: f32 },
+    /// Takes the damage instead of the health, until `amount` is used up
+    Shield { amount: f32, secs: f32 },
+}
+
+/// The value of an active effect and how long it lasts
+struct Lasting {
+    value: f32,
+    timer: Timer,
+}
+
+impl Lasting {
+    fn new(value: f32, secs: f32) -> Self {
+        Self {
+            value,
+            timer: Timer::from_seconds(secs, false),
+        }
+    }
+
+    fn remaining_secs(&self) -> f32 {
+        self.timer.duration().as_secs_f32() - self.timer.elapsed_secs()
+    }
+
+    /// Keeps the stronger value, by `is_stronger`, and the longer duration
+    fn refresh(current: &mut Option<Lasting>, value: f32, secs: f32, is_stronger: bool) {
+        *current = Some(match current.take() {
+            Some(lasting) => Lasting::new(
+                if is_stronger { value } else { lasting.value },
+                lasting.remaining_secs().max(secs),
+            ),
+            None => Lasting::new(value, secs),
+        });
+    }
+}
+
+/// The status effects on a sheep or war machine. Attach it on spawn, like `HitFeedback`, so
+/// effects never have to be inserted on entities that might die this frame.
+#[derive(Component, Default)]
+pub struct StatusEffects {
+    stun: Option<Lasting>,
+    slow: Option<Lasting>,
+    burn: Option<Lasting>,
+    poison: Option<Lasting>,
+    shield: Option<Lasting>,
+}
+
+impl StatusEffects {
+    /// The same effect doesn't stack, the stronger one stays for the longer duration
+    pub fn apply(&mut self, effect: StatusEffect) {
+        match effect {
+            StatusEffect::Stun { secs } => Lasting::refresh(&mut self.stun, 0.0, secs, false),
+            StatusEffect::Slow { factor, secs } => {
+                let is_stronger = self.slow.as_ref().map_or(true, |slow| factor < slow.value);
+                Lasting::refresh(&mut self.slow, factor, secs, is_stronger);
+            }
+            StatusEffect::Burn { per_sec, secs } => {
+                let is_stronger = self.burn.as_ref().map_or(true, |burn| per_sec > burn.value);
+                Lasting::refresh(&mut self.burn, per_sec, secs, is_stronger);
+            }
+            StatusEffect::Poison { per_sec, secs } => {
+                let is_stronger = self
+                    .poison
+                    .as_ref()
+                    .map_or(true, |poison| per_sec > poison.value);
+                Lasting::refresh(&mut self.poison, per_sec, secs, is_stronger);
+            }
+            StatusEffect::Shield { amount, secs } => {
+                let is_stronger = self
+                    .shield
+                    .as_ref()
+                    .map_or(true, |shield| amount > shield.value);
+                Lasting::refresh(&mut self.shield, amount, secs, is_stronger);
+            }
+        }
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.stun.is_some()
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.is_some()
+    }
+
+    /// Stunned entities can't move or attack at all, slowed ones move and attack slower, so
+    /// they deal less damage
+    fn stats_modifier(&self) -> Option<Modifier> {
+        if self.is_stunned() {
+            return Some(Modifier::multiply(0.0));
+        }
+        self.slow
+            .as_ref()
+            .map(|slow| Modifier::multiply(slow.value))
+    }
+
+    /// Reduces the `health` by the `amount` the shield doesn't take, returns the damage taken
+    pub fn damage(&mut self, health: &mut Health, amount: f32) -> f32 {
+        let mut taken = amount;
+        if let Some(shield) = self.shield.as_mut() {
+            let absorbed = shield.value.min(taken);
+            shield.value -= absorbed;
+            taken -= absorbed;
+            if shield.value <= 0.0 {
+                self.shield = None;
+            }
+        }
+
+        health.current -= taken;
+        taken
+    }
+
+    /// Runs the timers, returns the damage of burn and poison over the `delta`
+    fn tick(&mut self, delta: std::time::Duration) -> f32 {
+        let mut damage = 0.0;
+        for (lasting, deals_damage) in [
+            (&mut self.stun, false),
+            (&mut self.slow, false),
+            (&mut self.burn, true),
+            (&mut self.poison, true),
+            (&mut self.shield, false),
+        ] {
+            if let Some(effect) = lasting.as_mut() {
+                if deals_damage {
+                    damage += effect.value * delta.as_secs_f32();
+                }
+                if effect.timer.tick(delta).finished() {
+                    *lasting = None;
+                }
+            }
+        }
+        damage
+    }
+}
+
+/// Which side an area attack hits
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Side {
+    Sheep,
+    WarMachines,
+}
+
+/// Hits every entity of the `targets` side within the `radius`
+#[derive(Copy, Clone)]
+pub struct AreaAttack {
+    pub radius: f32,
+    pub damage: f32,
+    pub effect: Option<StatusEffect>,
+    pub targets: Side,
+}
+
+/// Sent to do an `AreaAttack` around the `center`
+pub struct AreaAttackEvent {
+    pub center: Vec2,
+    pub attack: AreaAttack,
+}
+
+/// Does its area attacks around itself, each one every few seconds
+#[derive(Component)]
+pub struct AreaAttacker {
+    attacks: Vec<(AreaAttack, Timer)>,
+}
+
+impl AreaAttacker {
+    /// Takes the attacks with the seconds between them
+    pub fn new(attacks: impl IntoIterator<Item = (AreaAttack, f32)>) -> Self {
+        Self {
+            attacks: attacks
+                .into_iter()
+                .map(|(attack, every_secs)| (attack, Timer::from_seconds(every_secs, true)))
+                .collect(),
+        }
+    }
+}
+
+/// A circle showing where an area attack hit, fades out with its timer
+#[derive(Component)]
+struct AreaFlash(Timer);
+
+struct AreaGraphics {
+    circle: Mesh2dHandle,
+}
+
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AreaAttackEvent>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_graphics)
+            .add_enter_system(GameState::Battle, add_area_attacks_to_sheep)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_if(battle_running)
+                    .label("update")
+                    .with_system(tick_status_effects)
+                    .with_system(area_attackers)
+                    .with_system(resolve_area_attacks)
+                    .with_system(fade_area_flashes)
+                    .into(),
+            )
+            .add_exit_system(GameState::Battle, reset_status_effects);
+    }
+}
+
+fn load_graphics(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(AreaGraphics {
+        circle: meshes.add(shape::Circle::new(1.0).into()).into(),
+    });
+}
+
+/// Tanks and medics get their area attacks for the battle
+fn add_area_attacks_to_sheep(mut commands: Commands, sheep_q: Query<(Entity, &Sheep)>) {
+    for (entity, sheep) in sheep_q.iter() {
+        let attacks = sheep.area_attacks();
+        if !attacks.is_empty() {
+            commands.entity(entity).insert(AreaAttacker::new(attacks));
+        }
+    }
+}
+
+fn tick_status_effects(
+    time: Res<Time>,
+    mut q: Query<(&mut StatusEffects, &mut Health, &mut Stats), Without<Dying>>,
+) {
+    for (mut effects, mut health, mut stats) in q.iter_mut() {
+        let damage = effects.tick(time.delta());
+        if damage > 0.0 {
+            effects.damage(&mut health, damage);
+        }
+
+        // Only touch the stats when the modifier changed, so they aren't recomputed every frame
+        let modifier = effects.stats_modifier();
+        for stat in [Stat::Speed, Stat::AttackDamage] {
+            if stats.modifier(ModifierSource::Status, stat) == modifier {
+                continue;
+            }
+            match modifier {
+                Some(modifier) => stats.set(ModifierSource::Status, stat, modifier),
+                None => stats.remove(ModifierSource::Status, stat),
+            }
+        }
+    }
+}
+
+/// Stunned attackers don't get to their next area attack
+fn area_attackers(
+    time: Res<Time>,
+    mut attackers_q: Query<(&Transform, &mut AreaAttacker, &StatusEffects), Without<Dying>>,
+    mut area_attacks: EventWriter<AreaAttackEvent>,
+) {
+    for (transform, mut attacker, effects) in attackers_q.iter_mut() {
+        if effects.is_stunned() {
+            continue;
+        }
+
+        for (attack, timer) in attacker.attacks.iter_mut() {
+            if timer.tick(time.delta()).just_finished() {
+                area_attacks.send(AreaAttackEvent {
+                    center: transform.translation.truncate(),
+                    attack: *attack,
+                });
+            }
+        }
+    }
+}
+
+fn resolve_area_attacks(
+    mut commands: Commands,
+    graphics: Res<AreaGraphics>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut area_attacks: EventReader<AreaAttackEvent>,
+    mut targets_q: Query<
+        (
+            Entity,
+            &Transform,
+            &mut Health,
+            &mut StatusEffects,
+            Option<&WarMachine>,
+        ),
+        Without<Dying>,
+    >,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for AreaAttackEvent { center, attack } in area_attacks.iter() {
+        for (entity, transform, mut health, mut effects, war_machine) in targets_q.iter_mut() {
+            let side = match war_machine {
+                Some(_) => Side::WarMachines,
+                None => Side::Sheep,
+            };
+            let difference = transform.translation.truncate() - *center;
+            if side != attack.targets
+                || health.current <= 0.0
+                || difference.length() > attack.radius
+            {
+                continue;
+            }
+
+            if attack.damage > 0.0 {
+                let taken = effects.damage(&mut health, attack.damage);
+                if taken > 0.0 {
+                    damage_events.send(DamageEvent {
+                        target: entity,
+                        amount: taken,
+                        direction: difference,
+                    });
+                }
+            }
+            if let Some(effect) = attack.effect {
+                effects.apply(effect);
+            }
+        }
+
+        // Attacks on sheep are red, the ones helping them blue, and the ones of sheep yellow
+        let color = match (attack.targets, attack.damage > 0.0) {
+            (Side::Sheep, true) => Color::rgba(1.0, 0.2, 0.1, 0.4),
+            (Side::Sheep, false) => Color::rgba(0.3, 0.6, 1.0, 0.4),
+            (Side::WarMachines, _) => Color::rgba(1.0, 0.9, 0.2, 0.4),
+        };
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: graphics.circle.clone(),
+                material: materials.add(ColorMaterial::from(color)),
+                transform: Transform::from_translation(center.extend(AREA_FLASH_Z))
+                    .with_scale(Vec3::new(attack.radius, attack.radius, 1.0)),
+                ..default()
+            })
+            .insert(AreaFlash(Timer::from_seconds(AREA_FLASH_SECS, false)))
+            .insert(UnloadOnExit)
+            .insert(Name::from("AreaFlash"));
+    }
+}
+
+fn fade_area_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut flash_q: Query<(Entity, &mut AreaFlash, &Handle<ColorMaterial>)>,
+) {
+    for (entity, mut flash, material) in flash_q.iter_mut() {
+        if flash.0.tick(time.delta()).finished() {
+            materials.remove(material);
+            commands.entity(entity).despawn_recursive();
+        } else if let Some(material) = materials.get_mut(material) {
+            material.color.set_a(0.4 * (1.0 - flash.0.percent()));
+        }
+    }
+}
+
+/// Sheep leave their effects and area attacks on the battlefield
+fn reset_status_effects(
+    mut commands: Commands,
+    mut q: Query<(Entity, &mut StatusEffects, &mut Stats), With<Sheep>>,
+) {
+    for (entity, mut effects, mut stats) in q.iter_mut() {
+        *effects = StatusEffects::default();
+        stats.clear(ModifierSource::Status);
+        commands.entity(entity).remove::<AreaAttacker>();
+    }
+}
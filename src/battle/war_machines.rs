@@ -1,18 +1,22 @@
-use crate::audio::AnimationAudioPlayback;
+use crate::audio::{AnimationAudioPlayback, SoundCue};
 use crate::utils::Speed;
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 use iyes_loopless::prelude::*;
 
-use super::{BATTLEFIELD_BOUNDS_X, BATTLEFIELD_BOUNDS_Y};
+use super::{battle_running, Level, BATTLEFIELD_BOUNDS_X, BATTLEFIELD_BOUNDS_Y};
 
+use super::damage::{DamageEvent, HitFeedback};
 use super::health_bars::create_war_machine_hp_bar;
+use super::status::{AreaAttacker, StatusEffects};
 
 use crate::animation::{Animation, Sheet};
-use crate::battle::states::{Attacking, Dying, Idling, Walking};
+use crate::battle::archetypes::{Archetype, Repairer};
+use crate::battle::boss::Boss;
+use crate::battle::states::{Attacking, Casting, Dying, Idling, Walking};
 use crate::sheep::Sheep;
+use crate::stats::{BaseStats, Modifier, ModifierSource, Stat, Stats};
 use crate::utils::{Attack, BehaviourType, Bounds, Health, UnloadOnExit};
-use crate::GameState;
 
 // Every WarMachine is defined by:
 // - `SpottingRange`: if a sheep is found within this radius, it will be pursued
@@ -25,6 +29,10 @@ use crate::GameState;
 #[derive(Component, Default)]
 pub struct WarMachine;
 
+/// Every level of the game after the first adds this much to the health and attack damage of the
+/// war machines
+const DIFFICULTY_PER_LEVEL: f32 = 0.1;
+
 pub struct WarMachinePlugin;
 
 impl Plugin for WarMachinePlugin {
@@ -32,137 +40,145 @@ impl Plugin for WarMachinePlugin {
         app.add_startup_system_to_stage(StartupStage::PreStartup, load_war_machine_graphics)
             .add_system_set(
                 ConditionSet::new()
-                    .run_in_state(GameState::Battle)
+                    .run_if(battle_running)
                     .with_system(idling)
                     .with_system(walking)
                     .with_system(attacking)
                     .with_system(dying)
+                    .with_system(repair_war_machines)
                     .into(),
             );
     }
 }
 
+/// Spawns a war machine of the `archetype`, with its stats scaled to the `level` and animations
 pub fn new_war_machine(
     commands: &mut Commands,
     animations_map: &Res<RobotAnimations>,
+    archetype: Archetype,
+    level: &Level,
     transform: Transform,
 ) -> Entity {
+    let data = archetype.data();
+    let animations = animations_map.0.get(&archetype).unwrap();
+    let mut stats = Stats::new(BaseStats {
+        speed: data.speed,
+        health: data.health,
+        attack_damage: data.attack_damage,
+        attack_range: data.attack_range,
+        spotting_range: 1000.0,
+    });
+    let difficulty =
+        Modifier::multiply(1.0 + DIFFICULTY_PER_LEVEL * level.0.saturating_sub(1) as f32);
+    stats.set(ModifierSource::Difficulty, Stat::Health, difficulty);
+    stats.set(ModifierSource::Difficulty, Stat::AttackDamage, difficulty);
+
     let id = commands
         .spawn_bundle(SpriteSheetBundle {
-            transform,
-            texture_atlas: animations_map
-                .0
+            transform: transform.with_scale(Vec3::splat(data.scale)),
+            texture_atlas: animations
                 .get(Idling::ANIMATION)
                 .unwrap()
                 .atlas_handle
                 .clone(),
             ..default()
         })
-        .insert(Animation::new(0.1, animations_map.0.clone()))
+        .insert(Animation::new(0.1, animations.clone()))
         .insert(Idling)
         .insert(WarMachine)
+        .insert(archetype)
+        .insert(stats.speed())
+        .insert(stats.health())
+        .insert(stats.attack())
+        .insert(stats)
+        .insert(data.behaviour)
+        .insert(HitFeedback::default())
+        .insert(StatusEffects::default())
         .insert(UnloadOnExit)
         .insert(Bounds {
             x: (BATTLEFIELD_BOUNDS_X.x, BATTLEFIELD_BOUNDS_X.y),
             y: (BATTLEFIELD_BOUNDS_Y.x, BATTLEFIELD_BOUNDS_Y.y),
         })
+        .insert(Name::from(data.name))
         .id();
 
-    create_war_machine_hp_bar(id, commands);
+    if let Some(repairer) = data.repair {
+        commands.entity(id).insert(repairer);
+    }
+    if let Some(area_attack) = data.area_attack {
+        commands.entity(id).insert(AreaAttacker::new([area_attack]));
+    }
+
+    // Bosses show their health at the top of the screen
+    if archetype == Archetype::Boss {
+        commands.entity(id).insert(Boss::default());
+    } else {
+        create_war_machine_hp_bar(id, commands);
+    }
 
     id
 }
 
-pub struct RobotAnimations(HashMap<String, Sheet>);
-
-pub fn load_war_machine_graphics(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) {
-    let mut animations_map: HashMap<String, Sheet> = HashMap::new();
-    let texture_handle = asset_server.load("EatingRobot.png");
-
-    // Add idling animation
+/// The animations of every archetype
+pub struct RobotAnimations(HashMap<Archetype, HashMap<String, Sheet>>);
+
+/// A row of 16x32 frames on the `texture`, starting at `offset`
+fn robot_sheet(
+    texture_atlases: &mut Assets<TextureAtlas>,
+    texture_handle: &Handle<Image>,
+    frames: usize,
+    offset: Vec2,
+    repeating: bool,
+) -> Sheet {
     let texture_atlas = TextureAtlas::from_grid_with_padding(
         texture_handle.clone(),
         Vec2::new(16.0, 32.0),
-        4,
+        frames,
         1,
         Vec2::ZERO,
-        Vec2::new(0.0, 0.0),
+        offset,
     );
 
     let len = texture_atlas.len();
-    animations_map.insert(
-        Idling::ANIMATION.to_owned(),
-        Sheet {
-            atlas_handle: texture_atlases.add(texture_atlas),
-            length: len,
-            repeating: true,
-        },
-    );
-
-    // Add walking animation
-    let texture_atlas = TextureAtlas::from_grid_with_padding(
-        texture_handle.clone(),
-        Vec2::new(16.0, 32.0),
-        7,
-        1,
-        Vec2::ZERO,
-        Vec2::new(0.0, 32.0),
-    );
-
-    let len = texture_atlas.len();
-    animations_map.insert(
-        Walking::ANIMATION.to_owned(),
-        Sheet {
-            atlas_handle: texture_atlases.add(texture_atlas),
-            length: len,
-            repeating: true,
-        },
-    );
+    Sheet {
+        atlas_handle: texture_atlases.add(texture_atlas),
+        length: len,
+        repeating,
+    }
+}
 
-    // Add attacking animation
-    let texture_atlas = TextureAtlas::from_grid_with_padding(
-        texture_handle.clone(),
-        Vec2::new(16.0, 32.0),
-        4,
-        1,
-        Vec2::ZERO,
-        Vec2::new(0.0, 64.0),
-    );
+pub fn load_war_machine_graphics(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let mut archetypes = HashMap::new();
+    for archetype in Archetype::ALL {
+        let data = archetype.data();
+        let texture = asset_server.load(data.sheet);
+        let mut animations_map: HashMap<String, Sheet> = HashMap::new();
+        let mut add = |name: &str, frames, row: f32, repeating| {
+            animations_map.insert(
+                name.to_owned(),
+                robot_sheet(
+                    &mut texture_atlases,
+                    &texture,
+                    frames,
+                    Vec2::new(0.0, 32.0 * row),
+                    repeating,
+                ),
+            );
+        };
 
-    let len = texture_atlas.len();
-    animations_map.insert(
-        Attacking::ANIMATION.to_owned(),
-        Sheet {
-            atlas_handle: texture_atlases.add(texture_atlas),
-            length: len,
-            repeating: true,
-        },
-    );
+        add(Idling::ANIMATION, 4, 0.0, true);
+        add(Walking::ANIMATION, data.walk_frames, 1.0, true);
+        add(Attacking::ANIMATION, 4, 2.0, true);
+        add(Dying::ANIMATION, 4, 3.0, false);
 
-    // Add dying animation
-    let texture_atlas = TextureAtlas::from_grid_with_padding(
-        texture_handle.clone(),
-        Vec2::new(16.0, 32.0),
-        4,
-        1,
-        Vec2::ZERO,
-        Vec2::new(0.0, 96.0),
-    );
+        archetypes.insert(archetype, animations_map);
+    }
 
-    let len = texture_atlas.len();
-    animations_map.insert(
-        Dying::ANIMATION.to_owned(),
-        Sheet {
-            atlas_handle: texture_atlases.add(texture_atlas),
-            length: len,
-            repeating: false,
-        },
-    );
-    commands.insert_resource(RobotAnimations(animations_map));
+    commands.insert_resource(RobotAnimations(archetypes));
 }
 
 fn idling(
@@ -170,7 +186,12 @@ fn idling(
     mut sheep_q: Query<(&mut Health, &mut Transform), (With<Sheep>, Without<WarMachine>)>,
     mut war_machines_q: Query<
         (Entity, &Transform, &Attack, &mut Animation),
-        (With<Idling>, With<WarMachine>, Without<Sheep>),
+        (
+            With<Idling>,
+            With<WarMachine>,
+            Without<Sheep>,
+            Without<Casting>,
+        ),
     >,
 ) {
     for (wm_entity, wm_transform, attack, mut animation) in war_machines_q.iter_mut() {
@@ -201,7 +222,7 @@ fn idling(
 
 fn walking(
     mut commands: Commands,
-    mut sheep_q: Query<&mut Transform, (With<Sheep>, Without<WarMachine>)>,
+    sheep_q: Query<(Entity, &Transform, &Health), (With<Sheep>, Without<WarMachine>)>,
     mut war_machines_q: Query<
         (
             Entity,
@@ -209,38 +230,49 @@ fn walking(
             &Attack,
             &BehaviourType,
             &Speed,
+            &StatusEffects,
             &mut Animation,
         ),
-        (With<Walking>, With<WarMachine>, Without<Sheep>),
+        (
+            With<Walking>,
+            With<WarMachine>,
+            Without<Sheep>,
+            Without<Casting>,
+        ),
     >,
     time: Res<Time>,
 ) {
-    for (wm_entity, mut wm_transform, attack, behaviour_type, speed, mut animation) in
+    for (wm_entity, mut wm_transform, attack, behaviour_type, speed, effects, mut animation) in
         war_machines_q.iter_mut()
     {
         // Start animation if we have not yet
         if animation.current_animation.as_deref() != Some(Walking::ANIMATION) {
             animation.play(Walking::ANIMATION, true);
 
-            // Add spotting sound on 2nd frame to not be to obnoxious
-            //commands
-            //.entity(wm_entity)
-            //.insert(AnimationAudioPlayback::new(
-            //Walking::ANIMATION.to_owned(),
-            //HashMap::from([(2, String::from("audio/robot_engaged.mp3"))]),
-            //));
+            // Add spotting sound on 2nd frame, its cooldown keeps it from being obnoxious
+            commands
+                .entity(wm_entity)
+                .insert(AnimationAudioPlayback::new(
+                    Walking::ANIMATION.to_owned(),
+                    HashMap::from([(2, SoundCue::WarMachineSpotted)]),
+                ));
+        }
+
+        // Stunned war machines stand still and don't start attacking
+        if effects.is_stunned() {
+            continue;
         }
 
         // Check whether any sheep are within spotting_range
-        let mut sheep = sheep_q
-            .iter_mut()
-            .filter(|sheep_transform| {
-                wm_transform
-                    .translation
-                    .truncate()
-                    .distance(sheep_transform.translation.truncate())
-                    <= attack.spotting_range
-            })
+        let distance = |sheep_transform: &Transform| {
+            wm_transform
+                .translation
+                .truncate()
+                .distance(sheep_transform.translation.truncate())
+        };
+        let sheep = sheep_q
+            .iter()
+            .filter(|(_, sheep_transform, _)| distance(sheep_transform) <= attack.spotting_range)
             .collect::<Vec<_>>();
 
         // Transition to Idling if no sheep are found
@@ -250,45 +282,50 @@ fn walking(
             continue;
         }
 
-        // Otherwise check if we can attack any of the sheep
-        sheep.sort_by(|transform1, transform2| {
-            wm_transform
-                .translation
-                .truncate()
-                .distance(transform1.translation.truncate())
-                .partial_cmp(
-                    &wm_transform
-                        .translation
-                        .truncate()
-                        .distance(transform2.translation.truncate()),
-                )
-                .unwrap()
-        });
+        // Otherwise pick the sheep to go after depending on the `behaviour_type`
+        let target =
+            match behaviour_type {
+                BehaviourType::ChasingClosest | BehaviourType::KeepingDistance => sheep
+                    .iter()
+                    .min_by(|(_, transform1, _), (_, transform2, _)| {
+                        distance(transform1).total_cmp(&distance(transform2))
+                    }),
+                // The most wounded sheep, no matter how much health it has in total
+                BehaviourType::ChasingWeakest => {
+                    sheep.iter().min_by(|(_, _, health1), (_, _, health2)| {
+                        health1.ratio().total_cmp(&health2.ratio())
+                    })
+                }
+            };
 
-        // Find the closest sheep
-        if let Some(sheep_transform) = sheep.get_mut(0) {
+        if let Some((sheep_entity, sheep_transform, _)) = target {
             let difference =
                 sheep_transform.translation.truncate() - wm_transform.translation.truncate();
+            let direction = difference.normalize_or_zero();
+
+            // Sheep that come too close are left behind by war machines keeping their distance
+            if matches!(behaviour_type, BehaviourType::KeepingDistance)
+                && difference.length() < attack.attack_range * 0.5
+            {
+                animation.flip_x = direction.x > 0.0;
+                wm_transform.translation -= direction.extend(0.0) * speed.0 * time.delta_seconds();
+                continue;
+            }
 
             // If the sheep is within attack_range, transition into Attacking state
             if difference.length() <= attack.attack_range {
                 commands.entity(wm_entity).remove::<Walking>();
-                commands.entity(wm_entity).insert(Attacking::default());
+                commands
+                    .entity(wm_entity)
+                    .insert(Attacking::new(*sheep_entity));
                 continue;
             }
 
-            // Oterwise move towards the sheep depending on the `behaviour_type`
-            match behaviour_type {
-                BehaviourType::ChasingClosest => {
-                    let direction = difference.normalize_or_zero();
-
-                    if difference.length() >= attack.attack_range * 0.5 {
-                        animation.flip_x = direction.x <= 0.0;
+            // Oterwise move towards the sheep
+            if difference.length() >= attack.attack_range * 0.5 {
+                animation.flip_x = direction.x <= 0.0;
 
-                        wm_transform.translation +=
-                            direction.extend(0.0) * speed.0 * time.delta_seconds();
-                    }
-                }
+                wm_transform.translation += direction.extend(0.0) * speed.0 * time.delta_seconds();
             }
         }
     }
@@ -296,19 +333,30 @@ fn walking(
 
 fn attacking(
     mut commands: Commands,
-    mut sheep_q: Query<(&mut Health, &mut Transform), (With<Sheep>, Without<WarMachine>)>,
+    mut sheep_q: Query<
+        (Entity, &mut Health, &mut StatusEffects, &Transform),
+        (With<Sheep>, Without<WarMachine>),
+    >,
     mut war_machines_q: Query<
         (
             Entity,
             &mut Transform,
             &Attack,
+            &Archetype,
             &mut Animation,
             &mut Attacking,
         ),
-        (With<Attacking>, With<WarMachine>, Without<Sheep>),
+        (
+            With<Attacking>,
+            With<WarMachine>,
+            Without<Sheep>,
+            Without<Casting>,
+        ),
     >,
+    mut damage_events: EventWriter<DamageEvent>,
 ) {
-    for (wm_entity, wm_transform, attack, mut animation, mut attacking) in war_machines_q.iter_mut()
+    for (wm_entity, wm_transform, attack, archetype, mut animation, mut attacking) in
+        war_machines_q.iter_mut()
     {
         if !attacking.has_started {
             attacking.has_started = true;
@@ -320,50 +368,41 @@ fn attacking(
                 .entity(wm_entity)
                 .insert(AnimationAudioPlayback::new(
                     Attacking::ANIMATION.to_owned(),
-                    HashMap::from([(1, String::from("audio/robot_eat.mp3"))]),
+                    HashMap::from([(1, SoundCue::WarMachineEat)]),
                 ));
 
-            // Check whether any sheep are within attack range
-            let mut sheep = sheep_q
-                .iter_mut()
-                .filter(|(_, sheep_transform)| {
-                    wm_transform
-                        .translation
-                        .truncate()
-                        .distance(sheep_transform.translation.truncate())
-                        <= attack.attack_range
-                })
-                .collect::<Vec<_>>();
-
-            // Transition to Idling if no sheep are found
-            if sheep.is_empty() {
-                commands.entity(wm_entity).remove::<Attacking>();
-                commands.entity(wm_entity).insert(Idling);
-                continue;
-            }
-
-            // Otherwise sort sheep to find the closest one to attack
-            sheep.sort_by(|(_, transform1), (_, transform2)| {
+            // Bite the sheep it walked up to, or go back to idling if it is gone or got away
+            let in_range = |sheep_transform: &Transform| {
                 wm_transform
                     .translation
                     .truncate()
-                    .distance(transform1.translation.truncate())
-                    .partial_cmp(
-                        &wm_transform
-                            .translation
-                            .truncate()
-                            .distance(transform2.translation.truncate()),
-                    )
-                    .unwrap()
-            });
-
-            // Attack the sheep
-            if let Some((ref mut sheep_health, sheep_transform)) = sheep.get_mut(0) {
-                let difference =
-                    sheep_transform.translation.truncate() - wm_transform.translation.truncate();
-
-                animation.flip_x = difference.normalize_or_zero().x <= 0.0;
-                sheep_health.current -= attack.attack_damage;
+                    .distance(sheep_transform.translation.truncate())
+                    <= attack.attack_range
+            };
+            let (sheep_entity, mut sheep_health, mut sheep_effects, sheep_transform) =
+                match sheep_q.get_mut(attacking.target) {
+                    Ok(target) if in_range(target.3) => target,
+                    _ => {
+                        commands.entity(wm_entity).remove::<Attacking>();
+                        commands.entity(wm_entity).insert(Idling);
+                        continue;
+                    }
+                };
+
+            let difference =
+                sheep_transform.translation.truncate() - wm_transform.translation.truncate();
+
+            animation.flip_x = difference.normalize_or_zero().x <= 0.0;
+            let taken = sheep_effects.damage(&mut sheep_health, attack.attack_damage);
+            if taken > 0.0 {
+                damage_events.send(DamageEvent {
+                    target: sheep_entity,
+                    amount: taken,
+                    direction: difference,
+                });
+                if let Some(effect) = archetype.data().on_hit {
+                    sheep_effects.apply(effect);
+                }
             }
         }
 
@@ -394,3 +433,38 @@ fn dying(
         }
     }
 }
+
+/// Repairers heal the war machines around them, but not themselves or poisoned ones
+fn repair_war_machines(
+    time: Res<Time>,
+    repairers_q: Query<(Entity, &Transform, &Repairer, &StatusEffects), Without<Dying>>,
+    mut war_machines_q: Query<
+        (Entity, &Transform, &mut Health, &StatusEffects),
+        (With<WarMachine>, Without<Dying>),
+    >,
+) {
+    for (repairer, repairer_transform, repair, repairer_effects) in repairers_q.iter() {
+        if repairer_effects.is_stunned() {
+            continue;
+        }
+
+        for (war_machine, transform, mut health, effects) in war_machines_q.iter_mut() {
+            if war_machine == repairer
+                || health.current <= 0.0
+                || health.current >= health.max
+                || effects.is_poisoned()
+            {
+                continue;
+            }
+
+            let distance = repairer_transform
+                .translation
+                .truncate()
+                .distance(transform.translation.truncate());
+            if distance <= repair.range {
+                health.current =
+                    (health.current + repair.per_sec * time.delta_seconds()).min(health.max);
+            }
+        }
+    }
+}
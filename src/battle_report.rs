@@ -3,7 +3,9 @@ use iyes_loopless::prelude::*;
 
 use crate::utils::UnloadOnExit;
 
-use crate::ui::{write_text, AsciiSheet};
+use crate::actions::{Action, Actions, InputMap};
+use crate::dialog::no_modal_open;
+use crate::ui::{write_hud_text, Anchor, AsciiSheet, HudAnchor};
 use crate::utils::despawn_entities_with_component;
 use crate::{GameState, NewGame};
 
@@ -24,33 +26,56 @@ pub struct BattleResult {
     pub war_machines_slain: usize,
     pub sheep_slain: usize,
     pub level_reward_sheep_gained: usize,
+    pub level_reward_wool_gained: usize,
+    /// Names of the sheep that died in the battle
+    pub fallen_sheep: Vec<String>,
 }
 
 impl BattleResult {
-    /// Return the final text depending on the BattleStatus
-    pub fn status_text(&self) -> String {
+    /// Return the final text depending on the BattleStatus, `continue_label` is the key to press
+    pub fn status_text(&self, continue_label: &str) -> String {
+        let fallen = self.fallen_text();
         match self.battle_status {
             BattleStatus::Victory => {
                 format!(
-                    "            You won!\n\n  Baaaa bye angry war machines!\n\n\n       You gain {} new sheep!\n\n\n     Press SPACE to continue.",
-                    self.level_reward_sheep_gained
+                    "            You won!\n\n  Baaaa bye angry war machines!\n\n\n       You gain {} new sheep\n          and {} wool!{fallen}\n\n\n     Press {continue_label} to continue.",
+                    self.level_reward_sheep_gained, self.level_reward_wool_gained
                 )
             }
             BattleStatus::GameOver => {
-                format!("           Game over! :(\n\n\n   Press SPACE to start a new game!")
+                format!(
+                    "           Game over! :({fallen}\n\n\n   Press {continue_label} to start a new game!"
+                )
             }
             BattleStatus::Draw => {
-                format!("           Time ran out!\n\nYou can face the war machines again\n  until all of your sheep are gone!\n\n\n     Press SPACE to continue.")
+                format!("           Time ran out!\n\nYou can face the war machines again\n  until all of your sheep are gone!{fallen}\n\n\n     Press {continue_label} to continue.")
             }
             _ => {
                 format!("Something unexpected happen. You should still be playing the game!")
             }
         }
     }
+
+    /// Remembers the sheep that died, empty if all of them made it
+    fn fallen_text(&self) -> String {
+        let names = match self.fallen_sheep.len() {
+            0 => return String::new(),
+            1..=3 => self.fallen_sheep.join(", "),
+            fallen => format!(
+                "{} and {} more",
+                self.fallen_sheep[..2].join(", "),
+                fallen - 2
+            ),
+        };
+        format!("\n\n{:^34}\n{names:^34}", "In memory of")
+    }
 }
 
-/// If this resource is present, `.0` many sheep will be added to the pen
-pub struct LevelReward(pub usize);
+/// If this resource is present, the sheep are added to the pen and the wool to the `Wool`
+pub struct LevelReward {
+    pub sheep: usize,
+    pub wool: usize,
+}
 
 pub struct BattleReportPlugin;
 
@@ -59,7 +84,7 @@ impl Plugin for BattleReportPlugin {
         app.add_system_set(
             ConditionSet::new()
                 .run_in_state(GameState::BattleReport)
-                .with_system(keyboard_input)
+                .with_system(keyboard_input.run_if(no_modal_open))
                 .into(),
         )
         .add_enter_system(GameState::BattleReport, setup_result_text)
@@ -77,10 +102,14 @@ fn setup_result_text(
     asset_server: Res<AssetServer>,
     ascii_sheet: Res<AsciiSheet>,
     battle_result: Res<BattleResult>,
+    input_map: Res<InputMap>,
 ) {
     let color: Color;
     if battle_result.battle_status == BattleStatus::Victory {
-        commands.insert_resource(LevelReward(battle_result.level_reward_sheep_gained));
+        commands.insert_resource(LevelReward {
+            sheep: battle_result.level_reward_sheep_gained,
+            wool: battle_result.level_reward_wool_gained,
+        });
         color = Color::WHITE;
     } else if battle_result.battle_status == BattleStatus::GameOver {
         commands.insert_resource(NewGame);
@@ -97,29 +126,28 @@ fn setup_result_text(
                 custom_size: Some(Vec2::new(550.0, 300.0) / 16.0),
                 ..default()
             },
-            transform: Transform {
-                translation: Vec3::new(0.0, 0.0, 100.0),
-                ..default()
-            },
             ..default()
         })
+        .insert(HudAnchor::new(Anchor::Center, Vec2::ZERO, 100.0))
         .insert(UnloadOnExit)
         .insert(Name::from("BattleReportBackground"));
 
-    let text = write_text(
+    let text = write_hud_text(
         &mut commands,
         &ascii_sheet,
-        Vec2::new(-4.0, 2.0).extend(120.0),
+        Anchor::Center,
+        Vec2::ZERO,
+        120.0,
         Color::WHITE,
-        &battle_result.status_text(),
+        &battle_result.status_text(&input_map.label(Action::Continue)),
     );
     commands.entity(text).insert(UnloadOnExit);
 
     commands.remove_resource::<BattleResult>();
 }
 
-fn keyboard_input(mut commands: Commands, keys: ResMut<Input<KeyCode>>) {
-    if keys.just_released(KeyCode::Space) {
+fn keyboard_input(mut commands: Commands, actions: Actions) {
+    if actions.just_released(Action::Continue) {
         commands.insert_resource(NextState(GameState::Herding));
     }
 }
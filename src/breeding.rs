@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::battle::Level;
+use crate::drag::Drag;
+use crate::pen::PenUpgrades;
+use crate::sheep::{spawn_sheep, Sheep, SheepParent, SheepSprites};
+use crate::ui::{write_text, AsciiSheet, LETTER_TILE_WIDTH};
+use crate::utils::{Bounds, UnloadOnExit};
+use crate::GameState;
+
+/// The breeding corner is in the top left of the pen
+const CORNER_SIZE: Vec2 = Vec2::new(2.5, 2.5);
+const BREEDING_TIME_SECS: f32 = 10.0;
+
+/// Sheep in the breeding corner stay there instead of wandering off
+#[derive(Component)]
+pub struct Breeding;
+
+/// The two sheep breeding in the corner and how long until their lamb is born
+#[derive(Default)]
+struct BreedingPair {
+    parents: Option<(Entity, Entity)>,
+    timer: Timer,
+}
+
+/// Marker component for the breeding corner
+#[derive(Component)]
+struct BreedingCorner;
+
+/// Marker component for the text above the breeding corner
+#[derive(Component)]
+struct BreedingText;
+
+pub struct BreedingPlugin;
+
+impl Plugin for BreedingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BreedingPair>()
+            .add_enter_system(GameState::Herding, spawn_breeding_corner)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Herding)
+                    .with_system(resize_breeding_corner)
+                    .with_system(mark_breeding_sheep)
+                    .with_system(breed_sheep)
+                    .with_system(update_breeding_text)
+                    .into(),
+            )
+            .add_exit_system(GameState::Herding, stop_breeding);
+    }
+}
+
+fn corner_bounds(pen: &Bounds) -> Bounds {
+    Bounds {
+        x: (pen.x.0, pen.x.0 + CORNER_SIZE.x),
+        y: (pen.y.1 - CORNER_SIZE.y, pen.y.1),
+    }
+}
+
+fn in_corner(corner: &Bounds, transform: &Transform) -> bool {
+    let position = transform.translation;
+    (corner.x.0..=corner.x.1).contains(&position.x)
+        && (corner.y.0..=corner.y.1).contains(&position.y)
+}
+
+fn spawn_breeding_corner(mut commands: Commands) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(1.0, 0.6, 0.8, 0.3),
+                custom_size: Some(CORNER_SIZE),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec2::ZERO.extend(1.5)),
+            ..default()
+        })
+        .insert(BreedingCorner)
+        .insert(UnloadOnExit)
+        .insert(Name::from("BreedingCorner"));
+}
+
+/// The corner moves along with the fence, when the pen grows
+fn resize_breeding_corner(
+    upgrades: Res<PenUpgrades>,
+    mut corner_q: Query<&mut Transform, With<BreedingCorner>>,
+    added_q: Query<(), Added<BreedingCorner>>,
+) {
+    if !upgrades.is_changed() && added_q.is_empty() {
+        return;
+    }
+
+    let corner = corner_bounds(&upgrades.pen_bounds());
+    let center = Vec2::new(
+        (corner.x.0 + corner.x.1) / 2.0,
+        (corner.y.0 + corner.y.1) / 2.0,
+    );
+    for mut transform in corner_q.iter_mut() {
+        transform.translation = center.extend(transform.translation.z);
+    }
+}
+
+/// Sheep dropped into the corner stay there to breed, picking them up takes them out again
+fn mark_breeding_sheep(
+    mut commands: Commands,
+    upgrades: Res<PenUpgrades>,
+    dropped: RemovedComponents<Drag>,
+    sheep_q: Query<(Entity, &Transform, Option<&Breeding>), With<Sheep>>,
+    dragged_q: Query<Entity, (With<Breeding>, With<Drag>)>,
+) {
+    let corner = corner_bounds(&upgrades.pen_bounds());
+
+    for dropped in dropped.iter() {
+        if let Ok((sheep, transform, _)) = sheep_q.get(dropped) {
+            if in_corner(&corner, transform) {
+                commands.entity(sheep).insert(Breeding);
+            }
+        }
+    }
+
+    for sheep in dragged_q.iter() {
+        commands.entity(sheep).remove::<Breeding>();
+    }
+
+    // The corner moves when the pen grows, the sheep are left behind
+    if upgrades.is_changed() {
+        for (sheep, transform, breeding) in sheep_q.iter() {
+            if breeding.is_some() && !in_corner(&corner, transform) {
+                commands.entity(sheep).remove::<Breeding>();
+            }
+        }
+    }
+}
+
+/// Two sheep in the corner get a lamb after a while, as long as it fits into the pen
+#[allow(clippy::too_many_arguments)]
+fn breed_sheep(
+    mut commands: Commands,
+    time: Res<Time>,
+    texture: Res<SheepSprites>,
+    level: Res<Level>,
+    upgrades: Res<PenUpgrades>,
+    mut pair: ResMut<BreedingPair>,
+    sheep_q: Query<(Entity, &Sheep, Option<&Breeding>)>,
+    sheep_parent: Query<Entity, With<SheepParent>>,
+) {
+    let mut breeding = sheep_q
+        .iter()
+        .filter(|(_, _, breeding)| breeding.is_some())
+        .map(|(entity, _, _)| entity)
+        .collect::<Vec<_>>();
+    breeding.sort();
+
+    let parents = match breeding[..] {
+        [first, second, ..] => (first, second),
+        _ => {
+            pair.parents = None;
+            return;
+        }
+    };
+
+    if pair.parents != Some(parents) {
+        pair.parents = Some(parents);
+        pair.timer = Timer::from_seconds(BREEDING_TIME_SECS, false);
+    }
+
+    if sheep_q.iter().count() >= upgrades.capacity() {
+        return;
+    }
+
+    if !pair.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    pair.timer.reset();
+
+    let (_, mother, _) = sheep_q.get(parents.0).unwrap();
+    let (_, father, _) = sheep_q.get(parents.1).unwrap();
+    let corner = corner_bounds(&upgrades.pen_bounds());
+
+    // The lamb is born right next to the corner, so it does not start breeding itself
+    let lamb = spawn_sheep(
+        &mut commands,
+        &texture,
+        Transform::from_xyz(corner.x.1 + 0.5, (corner.y.0 + corner.y.1) / 2.0, 10.0),
+        upgrades.pen_bounds(),
+        mother.breed(father, level.0),
+    );
+    commands.entity(sheep_parent.single()).add_child(lamb);
+}
+
+fn update_breeding_text(
+    mut commands: Commands,
+    ascii_sheet: Res<AsciiSheet>,
+    upgrades: Res<PenUpgrades>,
+    pair: Res<BreedingPair>,
+    all_sheep_q: Query<(), With<Sheep>>,
+    text_q: Query<Entity, With<BreedingText>>,
+    mut shown: Local<Option<String>>,
+) {
+    let text = match pair.parents {
+        Some(_) if all_sheep_q.iter().count() >= upgrades.capacity() => "Pen full".to_string(),
+        Some(_) => format!(
+            "Lamb in {}s",
+            (BREEDING_TIME_SECS - pair.timer.elapsed_secs()).ceil()
+        ),
+        None => "Breeding".to_string(),
+    };
+    if shown.as_ref() == Some(&text) && !text_q.is_empty() && !upgrades.is_changed() {
+        return;
+    }
+
+    text_q.for_each(|text| commands.entity(text).despawn_recursive());
+
+    let corner = corner_bounds(&upgrades.pen_bounds());
+    let width = text.chars().count() as f32 * LETTER_TILE_WIDTH / 16.0;
+    let position = Vec2::new((corner.x.0 + corner.x.1 - width) / 2.0, corner.y.1 + 0.8);
+    let entity = write_text(
+        &mut commands,
+        &ascii_sheet,
+        position.extend(21.0),
+        Color::WHITE,
+        &text,
+    );
+    commands
+        .entity(entity)
+        .insert(BreedingText)
+        .insert(UnloadOnExit);
+
+    *shown = Some(text);
+}
+
+fn stop_breeding(
+    mut commands: Commands,
+    mut pair: ResMut<BreedingPair>,
+    breeding_q: Query<Entity, With<Breeding>>,
+) {
+    pair.parents = None;
+    breeding_q.for_each(|sheep| {
+        commands.entity(sheep).remove::<Breeding>();
+    });
+}
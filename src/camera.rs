@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+use bevy::render::camera::{RenderTarget, Viewport};
+
+use crate::audio::AudioListener;
+
+/// Size of the playfield in world units, it is always fully visible
+pub const PLAYFIELD_SIZE: Vec2 = Vec2::new(32.0, 18.0);
+
+/// How far the camera can zoom into the playfield
+pub const MAX_CAMERA_ZOOM: f32 = 3.0;
+
+/// Marker component for the camera showing the playfield
+#[derive(Component)]
+pub struct MainCamera;
+
+/// Zoom of the `MainCamera`, at `1.0` the whole playfield is visible
+#[derive(Component)]
+pub struct CameraZoom(pub f32);
+
+/// World position of the cursor, computed once per frame for the camera rendering to the window
+/// the cursor is in. `None` if the cursor is outside of every camera's viewport.
+#[derive(Default)]
+pub struct CursorWorld {
+    pub position: Option<Vec2>,
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClearColor(Color::BLACK))
+            .init_resource::<CursorWorld>()
+            .add_startup_system(spawn_camera)
+            .add_system_to_stage(CoreStage::First, update_cursor_world.label("cursor_world"))
+            .add_system(letterbox_camera);
+    }
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert(MainCamera)
+        .insert(CameraZoom(1.0))
+        .insert(AudioListener)
+        .insert(Name::from("MainCamera"));
+}
+
+/// Returns the largest viewport with the aspect ratio of the playfield, centered in the window
+fn letterbox_viewport(window_size: UVec2) -> Viewport {
+    let aspect = PLAYFIELD_SIZE.x / PLAYFIELD_SIZE.y;
+    let window = window_size.as_vec2();
+
+    let size = if window.x / window.y > aspect {
+        Vec2::new(window.y * aspect, window.y)
+    } else {
+        Vec2::new(window.x, window.x / aspect)
+    };
+
+    Viewport {
+        physical_position: ((window - size) / 2.0).as_uvec2(),
+        physical_size: size.as_uvec2().max(UVec2::ONE),
+        depth: 0.0..1.0,
+    }
+}
+
+/// Keeps the playfield letterboxed at a fixed aspect ratio, whatever the size of the window. When
+/// zoomed in, the camera is kept from panning outside of the playfield.
+fn letterbox_camera(
+    windows: Res<Windows>,
+    mut camera_q: Query<
+        (
+            &mut Camera,
+            &mut OrthographicProjection,
+            &mut Transform,
+            &mut CameraZoom,
+        ),
+        With<MainCamera>,
+    >,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let window_size = UVec2::new(window.physical_width(), window.physical_height());
+    if window_size.x == 0 || window_size.y == 0 {
+        // Minimized
+        return;
+    }
+
+    let viewport = letterbox_viewport(window_size);
+    let scale = PLAYFIELD_SIZE.y / (viewport.physical_size.y as f32 / window.scale_factor() as f32);
+
+    for (mut camera, mut projection, mut transform, mut zoom) in camera_q.iter_mut() {
+        let current = camera
+            .viewport
+            .as_ref()
+            .map(|current| (current.physical_position, current.physical_size));
+        if current != Some((viewport.physical_position, viewport.physical_size)) {
+            camera.viewport = Some(viewport.clone());
+        }
+
+        if !(1.0..=MAX_CAMERA_ZOOM).contains(&zoom.0) {
+            zoom.0 = zoom.0.clamp(1.0, MAX_CAMERA_ZOOM);
+        }
+
+        if projection.scale != scale / zoom.0 {
+            projection.scale = scale / zoom.0;
+        }
+
+        let max_pan = PLAYFIELD_SIZE / 2.0 * (1.0 - 1.0 / zoom.0);
+        let pan = transform.translation.truncate();
+        if pan.clamp(-max_pan, max_pan) != pan {
+            transform.translation = pan.clamp(-max_pan, max_pan).extend(transform.translation.z);
+        }
+    }
+}
+
+/// Converts the cursor position in the `window` to world coordinates of the `camera`, `None` if the
+/// cursor is outside of the camera's viewport
+fn cursor_to_world(
+    window: &Window,
+    cursor: Vec2,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    // Logic here courtesy of bevy cheat book
+    // https://bevy-cheatbook.github.io/cookbook/cursor2world.html
+    let win_size = Vec2::new(window.width() as f32, window.height() as f32);
+
+    // The viewport position is measured from the top of the window, while the cursor is measured
+    // from the bottom
+    let (viewport_min, viewport_size) = match &camera.viewport {
+        Some(viewport) => {
+            let scale_factor = window.scale_factor() as f32;
+            let position = viewport.physical_position.as_vec2() / scale_factor;
+            let size = viewport.physical_size.as_vec2() / scale_factor;
+            (
+                Vec2::new(position.x, win_size.y - position.y - size.y),
+                size,
+            )
+        }
+        None => (Vec2::ZERO, win_size),
+    };
+
+    let ndc = ((cursor - viewport_min) / viewport_size) * 2.0 - Vec2::ONE;
+    if ndc.abs().max_element() > 1.0 {
+        return None;
+    }
+
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    Some(ndc_to_world.project_point3(ndc.extend(-1.0)).truncate())
+}
+
+/// Converts a `position` in the `window` to world coordinates with the highest priority camera
+/// rendering to the window there
+pub fn window_to_world(
+    window: &Window,
+    position: Vec2,
+    camera_q: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let mut cameras = camera_q
+        .iter()
+        .filter(
+            |(camera, _)| matches!(camera.target, RenderTarget::Window(id) if id == window.id()),
+        )
+        .collect::<Vec<_>>();
+    cameras.sort_by_key(|(camera, _)| std::cmp::Reverse(camera.priority));
+
+    cameras
+        .iter()
+        .find_map(|(camera, transform)| cursor_to_world(window, position, camera, transform))
+}
+
+/// Finds the window with the cursor and converts the cursor position to world coordinates
+fn update_cursor_world(
+    windows: Res<Windows>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut cursor_world: ResMut<CursorWorld>,
+) {
+    cursor_world.position = windows
+        .iter()
+        .find_map(|window| window.cursor_position().map(|cursor| (window, cursor)))
+        .and_then(|(window, cursor)| window_to_world(window, cursor, &camera_q));
+}
@@ -1,5 +1,10 @@
+use bevy::app::AppExit;
 use bevy::prelude::*;
 use bevy_inspector_egui::WorldInspectorPlugin;
+use iyes_loopless::prelude::*;
+
+use crate::actions::{Action, Actions};
+use crate::dialog::{no_modal_open, ConfirmDialog, DialogAction, DialogConfirmed};
 
 pub struct DebugPlugin;
 
@@ -7,7 +12,23 @@ impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
         if cfg!(debug_assertions) {
             app.add_plugin(WorldInspectorPlugin::new())
-                .add_system(bevy::window::close_on_esc);
+                .add_system(confirm_quit)
+                .add_system(quit.run_if(no_modal_open));
         }
     }
 }
+
+fn quit(mut commands: Commands, actions: Actions) {
+    if actions.just_released(Action::Quit) {
+        commands.insert_resource(ConfirmDialog::new("Quit the game?", DialogAction::Quit));
+    }
+}
+
+fn confirm_quit(mut confirmed: EventReader<DialogConfirmed>, mut exit: EventWriter<AppExit>) {
+    if confirmed
+        .iter()
+        .any(|DialogConfirmed(action)| *action == DialogAction::Quit)
+    {
+        exit.send(AppExit);
+    }
+}
@@ -0,0 +1,164 @@
+use std::any::TypeId;
+
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use iyes_loopless::prelude::*;
+
+use crate::actions::{Action, Actions, InputMap};
+use crate::ui::{text_size, write_hud_text, Anchor, AsciiSheet, HudAnchor};
+
+/// Destructive actions that have to be confirmed first
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DialogAction {
+    NewGame,
+    Quit,
+    /// Sells the sheep for wool in the shop
+    SellSheep(Entity),
+}
+
+/// If this resource is present, a modal dialog asks to confirm the `action`. Everything else
+/// ignores the input until it is answered.
+pub struct ConfirmDialog {
+    pub message: String,
+    pub action: DialogAction,
+}
+
+impl ConfirmDialog {
+    pub fn new(message: &str, action: DialogAction) -> Self {
+        Self {
+            message: message.to_string(),
+            action,
+        }
+    }
+}
+
+/// The modal screens and dialogs that are open, see `ModalApp::add_modal`
+#[derive(Default)]
+pub struct OpenModals(HashSet<TypeId>);
+
+/// Modal screens and dialogs are resources that exist while they are open. Registering them keeps
+/// `OpenModals` up to date, so input systems can wait for them without knowing what they are.
+pub trait ModalApp {
+    fn add_modal<M: Send + Sync + 'static>(&mut self) -> &mut Self;
+}
+
+impl ModalApp for App {
+    fn add_modal<M: Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.init_resource::<OpenModals>()
+            .add_system_to_stage(CoreStage::First, track_modal::<M>)
+    }
+}
+
+fn track_modal<M: Send + Sync + 'static>(
+    modal: Option<Res<M>>,
+    mut open_modals: ResMut<OpenModals>,
+) {
+    let id = TypeId::of::<M>();
+    match modal {
+        Some(_) if !open_modals.0.contains(&id) => {
+            open_modals.0.insert(id);
+        }
+        None if open_modals.0.contains(&id) => {
+            open_modals.0.remove(&id);
+        }
+        _ => (),
+    }
+}
+
+/// Run condition for systems reacting to input, which have to wait while any modal screen or
+/// dialog is open
+pub fn no_modal_open(open_modals: Res<OpenModals>) -> bool {
+    open_modals.0.is_empty()
+}
+
+/// Run condition for the input of the modal `M`, which waits while another one is open on top
+pub fn only_modal_open<M: Send + Sync + 'static>(open_modals: Res<OpenModals>) -> bool {
+    open_modals.0.len() == 1 && open_modals.0.contains(&TypeId::of::<M>())
+}
+
+/// Sent when the player confirms the dialog
+pub struct DialogConfirmed(pub DialogAction);
+
+/// Marker component for everything that is part of the dialog
+#[derive(Component)]
+struct DialogEntity;
+
+pub struct DialogPlugin;
+
+impl Plugin for DialogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_modal::<ConfirmDialog>()
+            .add_event::<DialogConfirmed>()
+            .add_system(despawn_dialog.run_unless_resource_exists::<ConfirmDialog>())
+            .add_system_set(
+                ConditionSet::new()
+                    .run_if_resource_exists::<ConfirmDialog>()
+                    .with_system(dialog_input)
+                    .with_system(render_dialog)
+                    .into(),
+            );
+    }
+}
+
+fn dialog_input(
+    mut commands: Commands,
+    actions: Actions,
+    dialog: Res<ConfirmDialog>,
+    mut confirmed: EventWriter<DialogConfirmed>,
+) {
+    if actions.just_released(Action::Confirm) {
+        confirmed.send(DialogConfirmed(dialog.action));
+        commands.remove_resource::<ConfirmDialog>();
+    } else if actions.just_released(Action::Back) {
+        commands.remove_resource::<ConfirmDialog>();
+    }
+}
+
+fn render_dialog(
+    mut commands: Commands,
+    dialog: Res<ConfirmDialog>,
+    input_map: Res<InputMap>,
+    ascii_sheet: Res<AsciiSheet>,
+    dialog_q: Query<Entity, With<DialogEntity>>,
+) {
+    if !dialog.is_changed() {
+        return;
+    }
+
+    dialog_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let text = format!(
+        "{}\n\n\n{} yes   {} no",
+        dialog.message,
+        input_map.label(Action::Confirm),
+        input_map.label(Action::Back)
+    );
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.0, 0.0, 0.0, 0.9),
+                custom_size: Some(text_size(&text) + Vec2::splat(1.5)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudAnchor::new(Anchor::Center, Vec2::ZERO, 170.0))
+        .insert(DialogEntity)
+        .insert(Name::from("DialogBackground"));
+
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Center,
+        Vec2::ZERO,
+        180.0,
+        Color::ORANGE_RED,
+        &text,
+    );
+    commands.entity(text).insert(DialogEntity);
+}
+
+fn despawn_dialog(mut commands: Commands, dialog_q: Query<Entity, With<DialogEntity>>) {
+    dialog_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
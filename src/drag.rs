@@ -1,19 +1,14 @@
 use bevy::prelude::*;
 use iyes_loopless::prelude::*;
 
-use crate::{GameState, ScreenToWorld};
+use crate::pointer::Pointer;
+use crate::GameState;
 
 #[derive(Component)]
 pub struct Drag;
 
-pub fn drag(
-    mut q: Query<&mut Transform, With<Drag>>,
-    windows: Res<Windows>,
-    camera: Query<(&Camera, &GlobalTransform)>,
-) {
-    let window = windows.get_primary().unwrap();
-    if let Some(mouse_pos) = window.cursor_position() {
-        let mouse_pos = mouse_pos.screen_to_world(windows, camera);
+pub fn drag(mut q: Query<&mut Transform, With<Drag>>, pointer: Res<Pointer>) {
+    if let Some(mouse_pos) = pointer.position {
         for mut transform in q.iter_mut() {
             transform.translation = mouse_pos.extend(transform.translation.z);
         }
@@ -24,6 +19,8 @@ pub struct DragPlugin;
 
 impl Plugin for DragPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(drag.run_in_state(GameState::Herding));
+        for state in [GameState::Herding, GameState::Deployment] {
+            app.add_system(drag.run_in_state(state));
+        }
     }
 }
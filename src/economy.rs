@@ -0,0 +1,400 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::actions::{Action, Actions, InputMap};
+use crate::battle::Level;
+use crate::battle_report::LevelReward;
+use crate::dialog::{
+    no_modal_open, only_modal_open, ConfirmDialog, DialogAction, DialogConfirmed, ModalApp,
+};
+use crate::pen::{PenUpgrades, MAX_PEN_SIZE};
+use crate::sheep::{spawn_n_sheep, Select, Sheep, SheepParent, SheepSprites, Trait};
+use crate::stats::{Modifier, ModifierSource, Stat, Stats};
+use crate::ui::{text_size, write_hud_text, Anchor, AsciiSheet, HudAnchor};
+use crate::utils::UnloadOnExit;
+use crate::{GameState, NewGame};
+
+/// Wool every level of a sheep grows per second in the pen
+const WOOL_PER_LEVEL_PER_SEC: f32 = 0.1;
+/// Every level of the shears upgrade makes the sheep grow this much more wool
+const SHEARS_WOOL_BONUS: f32 = 0.25;
+/// Every level of the hay upgrade gives every sheep this much more health
+const HAY_HEALTH_BONUS: f32 = 0.1;
+
+const SHEEP_PRICE: usize = 15;
+const TRAIT_ITEM_PRICE: usize = 40;
+/// The upgrades get more expensive with every level
+const SHEARS_PRICE: usize = 30;
+const HAY_PRICE: usize = 40;
+const PEN_SIZE_PRICE: usize = 50;
+/// Selling a sheep pays this much wool for every level
+const SHEEP_SELL_PRICE_PER_LEVEL: usize = 5;
+
+/// Wool is what the sheep are paid with, they grow it while they are in the pen
+#[derive(Default)]
+pub struct Wool(pub f32);
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ShopItem {
+    Sheep,
+    /// Levels up the trait of the selected sheep
+    TraitItem(Trait),
+    Shears,
+    Hay,
+    PenSize,
+    /// Sells the selected sheep, to make room in the pen
+    SellSheep,
+}
+
+impl ShopItem {
+    fn all() -> Vec<ShopItem> {
+        std::iter::once(ShopItem::Sheep)
+            .chain(Trait::ALL.into_iter().map(ShopItem::TraitItem))
+            .chain([
+                ShopItem::Shears,
+                ShopItem::Hay,
+                ShopItem::PenSize,
+                ShopItem::SellSheep,
+            ])
+            .collect()
+    }
+
+    fn label(&self, upgrades: &PenUpgrades) -> String {
+        match self {
+            ShopItem::Sheep => "Sheep".to_string(),
+            ShopItem::TraitItem(added) => format!("{} item", added.label()),
+            ShopItem::Shears => format!("Shears lvl {}", upgrades.shears + 1),
+            ShopItem::Hay => format!("Hay lvl {}", upgrades.hay + 1),
+            ShopItem::PenSize => format!("Pen lvl {}", upgrades.size + 1),
+            ShopItem::SellSheep => "Sell sheep".to_string(),
+        }
+    }
+
+    /// `None` if the item is sold out
+    fn price(&self, upgrades: &PenUpgrades) -> Option<usize> {
+        match self {
+            ShopItem::Sheep => Some(SHEEP_PRICE),
+            ShopItem::TraitItem(_) => Some(TRAIT_ITEM_PRICE),
+            ShopItem::Shears => Some(SHEARS_PRICE * (upgrades.shears + 1)),
+            ShopItem::Hay => Some(HAY_PRICE * (upgrades.hay + 1)),
+            ShopItem::PenSize if upgrades.size >= MAX_PEN_SIZE => None,
+            ShopItem::PenSize => Some(PEN_SIZE_PRICE * (upgrades.size + 1)),
+            ShopItem::SellSheep => Some(0),
+        }
+    }
+
+    fn price_label(&self, upgrades: &PenUpgrades) -> String {
+        match (self, self.price(upgrades)) {
+            (ShopItem::SellSheep, _) => format!("+{SHEEP_SELL_PRICE_PER_LEVEL} wool/lvl"),
+            (_, Some(price)) => format!("{price:>4} wool"),
+            (_, None) => "sold out".to_string(),
+        }
+    }
+}
+
+/// If this resource is present, the shop is open and everything else in the pen ignores the
+/// input
+pub struct Shop {
+    selected: usize,
+    /// Shown when something could not be bought
+    message: &'static str,
+}
+
+/// Marker component for everything that is part of the shop
+#[derive(Component)]
+struct ShopEntity;
+
+/// Marker component for the wool text
+#[derive(Component)]
+struct WoolText;
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Wool>()
+            .init_resource::<PenUpgrades>()
+            .add_modal::<Shop>()
+            .add_enter_system_set(
+                GameState::Herding,
+                ConditionSet::new()
+                    .with_system(reset_economy.run_if_resource_exists::<NewGame>())
+                    .with_system(add_level_reward_wool.run_if_resource_exists::<LevelReward>())
+                    .into(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Herding)
+                    .with_system(grow_wool)
+                    .with_system(update_wool_text)
+                    .with_system(open_shop.run_if(no_modal_open))
+                    .with_system(shop_input.run_if(only_modal_open::<Shop>))
+                    .with_system(render_shop.run_if_resource_exists::<Shop>())
+                    .with_system(sell_sheep)
+                    .into(),
+            )
+            .add_system(despawn_shop.run_unless_resource_exists::<Shop>())
+            .add_system(feed_hay)
+            .add_exit_system(GameState::Herding, close_shop);
+    }
+}
+
+fn reset_economy(mut commands: Commands) {
+    commands.insert_resource(Wool::default());
+    commands.insert_resource(PenUpgrades::default());
+}
+
+fn add_level_reward_wool(level_reward: Res<LevelReward>, mut wool: ResMut<Wool>) {
+    wool.0 += level_reward.wool as f32;
+}
+
+fn grow_wool(
+    time: Res<Time>,
+    upgrades: Res<PenUpgrades>,
+    mut wool: ResMut<Wool>,
+    sheep_q: Query<&Sheep>,
+) {
+    let levels = sheep_q.iter().map(|sheep| sheep.sum_levels()).sum::<f32>();
+    let bonus = 1.0 + SHEARS_WOOL_BONUS * upgrades.shears as f32;
+
+    wool.0 += levels * WOOL_PER_LEVEL_PER_SEC * bonus * time.delta_seconds();
+}
+
+/// Keeps the health bonus of the hay upgrade on every sheep, including the ones added later
+fn feed_hay(upgrades: Res<PenUpgrades>, mut sheep_q: Query<&mut Stats, With<Sheep>>) {
+    let modifier = Modifier::multiply(1.0 + HAY_HEALTH_BONUS * upgrades.hay as f32);
+    for mut stats in sheep_q.iter_mut() {
+        // Only touched when it differs, so the stats aren't recomputed every frame
+        if stats.modifier(ModifierSource::Upgrade, Stat::Health) != Some(modifier) {
+            stats.set(ModifierSource::Upgrade, Stat::Health, modifier);
+        }
+    }
+}
+
+fn update_wool_text(
+    mut commands: Commands,
+    ascii_sheet: Res<AsciiSheet>,
+    wool: Res<Wool>,
+    text_q: Query<Entity, With<WoolText>>,
+    mut shown: Local<Option<usize>>,
+) {
+    let amount = wool.0 as usize;
+    if *shown == Some(amount) && !text_q.is_empty() {
+        return;
+    }
+    *shown = Some(amount);
+
+    text_q.for_each(|text| commands.entity(text).despawn_recursive());
+
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::TopRight,
+        Vec2::new(-0.5, -1.5),
+        50.0,
+        Color::WHITE,
+        format!("Wool: {amount}").as_str(),
+    );
+    commands.entity(text).insert(WoolText).insert(UnloadOnExit);
+}
+
+fn open_shop(mut commands: Commands, actions: Actions) {
+    if actions.just_released(Action::Shop) {
+        commands.insert_resource(Shop {
+            selected: 0,
+            message: "",
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn shop_input(
+    mut commands: Commands,
+    actions: Actions,
+    mut shop: ResMut<Shop>,
+    mut wool: ResMut<Wool>,
+    mut upgrades: ResMut<PenUpgrades>,
+    texture: Res<SheepSprites>,
+    level: Res<Level>,
+    sheep_parent: Query<Entity, With<SheepParent>>,
+    select_q: Query<&Parent, With<Select>>,
+    mut sheep_q: Query<(&mut Sheep, &mut Stats)>,
+) {
+    if actions.just_released(Action::Shop) || actions.just_released(Action::Back) {
+        commands.remove_resource::<Shop>();
+        return;
+    }
+
+    let items = ShopItem::all();
+    if actions.just_released(Action::MenuUp) {
+        shop.selected = (shop.selected + items.len() - 1) % items.len();
+        shop.message = "";
+    } else if actions.just_released(Action::MenuDown) {
+        shop.selected = (shop.selected + 1) % items.len();
+        shop.message = "";
+    }
+
+    if !actions.just_released(Action::Confirm) {
+        return;
+    }
+
+    let item = items[shop.selected];
+    let price = match item.price(&upgrades) {
+        Some(price) => price,
+        None => {
+            shop.message = "Sold out!";
+            return;
+        }
+    };
+    if wool.0 < price as f32 {
+        shop.message = "Not enough wool!";
+        return;
+    }
+
+    let selected = select_q
+        .iter()
+        .map(|parent| parent.get())
+        .find(|sheep| sheep_q.get(*sheep).is_ok());
+
+    match item {
+        ShopItem::Sheep => {
+            if sheep_q.iter().count() >= upgrades.capacity() {
+                shop.message = "The pen is full!";
+                return;
+            }
+            let sheep = spawn_n_sheep(&mut commands, texture, &upgrades.pen_bounds(), level.0, 1);
+            commands.entity(sheep_parent.single()).push_children(&sheep);
+        }
+        ShopItem::TraitItem(added) => {
+            match selected.and_then(|sheep| sheep_q.get_mut(sheep).ok()) {
+                Some((mut sheep, mut stats)) => {
+                    sheep.add_trait(added);
+                    sheep.update_stats(&mut stats);
+                }
+                None => {
+                    shop.message = "Select a sheep first!";
+                    return;
+                }
+            }
+        }
+        ShopItem::Shears => upgrades.shears += 1,
+        ShopItem::Hay => upgrades.hay += 1,
+        ShopItem::PenSize => upgrades.size += 1,
+        ShopItem::SellSheep => {
+            match selected {
+                // Without any sheep, there would be nobody left to fight or grow wool
+                Some(_) if sheep_q.iter().count() <= 1 => {
+                    shop.message = "Keep your last sheep!";
+                }
+                Some(sold) => {
+                    let (sheep, ..) = sheep_q.get(sold).unwrap();
+                    commands.insert_resource(ConfirmDialog::new(
+                        &format!("Sell {} for {} wool?", sheep.name(), sell_price(sheep)),
+                        DialogAction::SellSheep(sold),
+                    ));
+                }
+                None => shop.message = "Select a sheep first!",
+            }
+            return;
+        }
+    }
+
+    wool.0 -= price as f32;
+    shop.message = "Thank you!";
+}
+
+fn sell_price(sheep: &Sheep) -> usize {
+    (sheep.sum_levels() * SHEEP_SELL_PRICE_PER_LEVEL as f32) as usize
+}
+
+/// Sells the sheep once the player confirmed it
+fn sell_sheep(
+    mut commands: Commands,
+    mut confirmed: EventReader<DialogConfirmed>,
+    mut wool: ResMut<Wool>,
+    mut shop: Option<ResMut<Shop>>,
+    sheep_q: Query<&Sheep>,
+) {
+    for DialogConfirmed(action) in confirmed.iter() {
+        if let DialogAction::SellSheep(sold) = action {
+            if let Ok(sheep) = sheep_q.get(*sold) {
+                wool.0 += sell_price(sheep) as f32;
+                commands.entity(*sold).despawn_recursive();
+                if let Some(shop) = shop.as_mut() {
+                    shop.message = "Sold!";
+                }
+            }
+        }
+    }
+}
+
+/// Redraws the shop whenever it or the upgrades change
+fn render_shop(
+    mut commands: Commands,
+    shop: Res<Shop>,
+    upgrades: Res<PenUpgrades>,
+    input_map: Res<InputMap>,
+    ascii_sheet: Res<AsciiSheet>,
+    shop_q: Query<Entity, With<ShopEntity>>,
+) {
+    if !shop.is_changed() && !upgrades.is_changed() {
+        return;
+    }
+
+    shop_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let items = ShopItem::all()
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            format!(
+                "{} {:<15}{}",
+                if i == shop.selected { ">" } else { " " },
+                item.label(&upgrades),
+                item.price_label(&upgrades)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let text = format!(
+        "              SHOP\n\n{items}\n\nTrait items go to the selected sheep,\nshears grow more wool, hay makes\nevery sheep healthier and a bigger\npen fits more sheep.\n\n{:^36}\n\n{}/{} select   {} buy   {} close",
+        shop.message,
+        input_map.label(Action::MenuUp),
+        input_map.label(Action::MenuDown),
+        input_map.label(Action::Confirm),
+        input_map.label(Action::Shop),
+    );
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.0, 0.0, 0.0, 0.85),
+                custom_size: Some(text_size(&text) + Vec2::splat(1.5)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudAnchor::new(Anchor::Center, Vec2::ZERO, 150.0))
+        .insert(ShopEntity)
+        .insert(Name::from("ShopBackground"));
+
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Center,
+        Vec2::ZERO,
+        160.0,
+        Color::WHITE,
+        &text,
+    );
+    commands.entity(text).insert(ShopEntity);
+}
+
+fn close_shop(mut commands: Commands) {
+    commands.remove_resource::<Shop>();
+}
+
+fn despawn_shop(mut commands: Commands, shop_q: Query<Entity, With<ShopEntity>>) {
+    shop_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
@@ -0,0 +1,240 @@
+use std::marker::PhantomData;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::actions::{Action, Actions};
+use crate::camera::PLAYFIELD_SIZE;
+use crate::dialog::no_modal_open;
+use crate::pointer::Pointer;
+use crate::sheep::Sheep;
+use crate::GameState;
+
+/// Stick deflection at which the stick counts as pushed
+const STICK_THRESHOLD: f32 = 0.5;
+/// World units per second the cursor moves while carrying a sheep
+const CURSOR_SPEED: f32 = 10.0;
+
+/// Buttons and sticks of all the connected gamepads, any of them can be used to play
+#[derive(SystemParam)]
+pub struct GamepadInput<'w, 's> {
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> GamepadInput<'w, 's> {
+    pub fn pressed(&self, button: GamepadButtonType) -> bool {
+        self.gamepads
+            .iter()
+            .any(|gamepad| self.buttons.pressed(GamepadButton::new(*gamepad, button)))
+    }
+
+    pub fn just_pressed(&self, button: GamepadButtonType) -> bool {
+        self.gamepads.iter().any(|gamepad| {
+            self.buttons
+                .just_pressed(GamepadButton::new(*gamepad, button))
+        })
+    }
+
+    pub fn just_released(&self, button: GamepadButtonType) -> bool {
+        self.gamepads.iter().any(|gamepad| {
+            self.buttons
+                .just_released(GamepadButton::new(*gamepad, button))
+        })
+    }
+
+    /// Any button released this frame on a connected gamepad
+    pub fn released_button(&self) -> Option<GamepadButtonType> {
+        self.buttons
+            .get_just_released()
+            .find(|button| self.gamepads.contains(&button.gamepad))
+            .map(|button| button.button_type)
+    }
+
+    /// Direction of the left sticks and D-pads combined, at most `1.0` long
+    pub fn direction(&self) -> Vec2 {
+        let mut direction = Vec2::ZERO;
+        for gamepad in self.gamepads.iter() {
+            let axis = |axis_type| {
+                self.axes
+                    .get(GamepadAxis::new(*gamepad, axis_type))
+                    .unwrap_or(0.0)
+            };
+            let pressed = |button_type| match self
+                .buttons
+                .pressed(GamepadButton::new(*gamepad, button_type))
+            {
+                true => 1.0,
+                false => 0.0,
+            };
+
+            direction += Vec2::new(
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            );
+            direction += Vec2::new(
+                pressed(GamepadButtonType::DPadRight) - pressed(GamepadButtonType::DPadLeft),
+                pressed(GamepadButtonType::DPadUp) - pressed(GamepadButtonType::DPadDown),
+            );
+        }
+
+        direction.clamp_length_max(1.0)
+    }
+}
+
+/// Cursor moved with the gamepad. While nothing is carried it snaps between the sheep, while a
+/// sheep is carried it moves freely.
+#[derive(Default)]
+pub struct GamepadCursor {
+    pub position: Option<Vec2>,
+    /// Whether the gamepad was used more recently than the mouse, the cursor is only shown then
+    pub active: bool,
+    /// Sheep the cursor is snapped to
+    target: Option<Entity>,
+    /// Whether the stick was pushed last frame, so every push moves to one sheep only
+    pushed: bool,
+}
+
+#[derive(Component)]
+struct GamepadCursorSprite;
+
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GamepadCursor>()
+            .add_startup_system(spawn_gamepad_cursor);
+
+        // The cursor is used wherever the sheep can be moved around
+        for state in [GameState::Herding, GameState::Deployment] {
+            app.add_system_to_stage(
+                CoreStage::First,
+                move_gamepad_cursor
+                    .run_in_state(state.clone())
+                    .run_if(no_modal_open)
+                    .before("pointer"),
+            )
+            .add_system(show_gamepad_cursor.run_in_state(state.clone()))
+            .add_exit_system(state, hide_gamepad_cursor);
+        }
+    }
+}
+
+fn spawn_gamepad_cursor(mut commands: Commands, assets: Res<AssetServer>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: assets.load("OutlineBox.png"),
+            sprite: Sprite {
+                color: Color::YELLOW,
+                custom_size: Some(Vec2::splat(24.0) / 16.0),
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(GamepadCursorSprite)
+        .insert(Name::from("GamepadCursor"));
+}
+
+/// Returns the closest sheep roughly in the `direction` from `from`
+fn next_sheep(
+    from: Vec2,
+    direction: Vec2,
+    current: Option<Entity>,
+    sheep_q: &Query<(Entity, &Transform), With<Sheep>>,
+) -> Option<Entity> {
+    let direction = direction.normalize_or_zero();
+
+    sheep_q
+        .iter()
+        .filter(|(entity, _)| Some(*entity) != current)
+        .filter_map(|(entity, transform)| {
+            let offset = transform.translation.truncate() - from;
+            let alignment = offset.normalize_or_zero().dot(direction);
+            // Prefer sheep straight ahead over closer ones off to the side
+            (alignment > 0.5).then(|| (entity, offset.length() / alignment))
+        })
+        .min_by(|(_, score1), (_, score2)| score1.partial_cmp(score2).unwrap())
+        .map(|(entity, _)| entity)
+}
+
+fn nearest_sheep(from: Vec2, sheep_q: &Query<(Entity, &Transform), With<Sheep>>) -> Option<Entity> {
+    sheep_q
+        .iter()
+        .min_by(|(_, transform1), (_, transform2)| {
+            from.distance(transform1.translation.truncate())
+                .partial_cmp(&from.distance(transform2.translation.truncate()))
+                .unwrap()
+        })
+        .map(|(entity, _)| entity)
+}
+
+fn move_gamepad_cursor(
+    time: Res<Time>,
+    gamepad: GamepadInput,
+    actions: Actions,
+    pointer: Res<Pointer>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut cursor: ResMut<GamepadCursor>,
+    sheep_q: Query<(Entity, &Transform), With<Sheep>>,
+) {
+    if cursor_moved.iter().count() > 0 {
+        cursor.active = false;
+    }
+
+    let direction = gamepad.direction();
+    let pushed = direction.length() >= STICK_THRESHOLD;
+    if pushed || actions.just_pressed(Action::Grab) {
+        cursor.active = true;
+    }
+
+    let from = cursor.position.unwrap_or(Vec2::ZERO);
+    if pointer.pressed() {
+        cursor.target = None;
+        cursor.position = Some(
+            (from + direction * CURSOR_SPEED * time.delta_seconds())
+                .clamp(-PLAYFIELD_SIZE / 2.0, PLAYFIELD_SIZE / 2.0),
+        );
+    } else {
+        if pushed && !cursor.pushed {
+            if let Some(next) = next_sheep(from, direction, cursor.target, &sheep_q) {
+                cursor.target = Some(next);
+            }
+        }
+
+        // The sheep wander around, so the cursor follows the one it is snapped to. If it is gone,
+        // e.g. merged into another one, the closest sheep is picked instead.
+        let target = cursor
+            .target
+            .filter(|target| sheep_q.get(*target).is_ok())
+            .or_else(|| nearest_sheep(from, &sheep_q));
+        cursor.target = target;
+        cursor.position = target
+            .and_then(|target| sheep_q.get(target).ok())
+            .map(|(_, transform)| transform.translation.truncate());
+    }
+
+    cursor.pushed = pushed;
+}
+
+fn show_gamepad_cursor(
+    cursor: Res<GamepadCursor>,
+    mut sprite_q: Query<(&mut Transform, &mut Visibility), With<GamepadCursorSprite>>,
+) {
+    for (mut transform, mut visibility) in sprite_q.iter_mut() {
+        visibility.is_visible = cursor.active && cursor.position.is_some();
+        if let Some(position) = cursor.position {
+            transform.translation = position.extend(35.0);
+        }
+    }
+}
+
+fn hide_gamepad_cursor(mut sprite_q: Query<&mut Visibility, With<GamepadCursorSprite>>) {
+    for mut visibility in sprite_q.iter_mut() {
+        visibility.is_visible = false;
+    }
+}
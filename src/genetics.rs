@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+
+use crate::sheep::Trait;
+
+/// Chance of two parents with the same rarity to get a rarer offspring
+const RARITY_UPGRADE_CHANCE: f64 = 0.1;
+/// How far the hue of the offspring can be off from the hue of its parents, in degrees
+const MAX_HUE_MUTATION: f32 = 15.0;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Pattern {
+    Plain,
+    /// Faster sheep
+    Spotted,
+    /// Sheep with a longer attack range
+    Striped,
+}
+
+impl Pattern {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Pattern::Plain => "plain",
+            Pattern::Spotted => "spotted",
+            Pattern::Striped => "striped",
+        }
+    }
+
+    pub fn speed_bonus(&self) -> f32 {
+        match self {
+            Pattern::Spotted => 1.15,
+            _ => 1.0,
+        }
+    }
+
+    pub fn attack_range_bonus(&self) -> f32 {
+        match self {
+            Pattern::Striped => 1.15,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Rarer sheep are stronger and more colourful
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Rarity {
+    Common,
+    Rare,
+    Legendary,
+}
+
+impl Rarity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Rarity::Common => "common",
+            Rarity::Rare => "rare",
+            Rarity::Legendary => "legendary",
+        }
+    }
+
+    /// Multiplies the attack damage and health
+    pub fn stat_bonus(&self) -> f32 {
+        match self {
+            Rarity::Common => 1.0,
+            Rarity::Rare => 1.1,
+            Rarity::Legendary => 1.25,
+        }
+    }
+
+    /// Rarer lambs are more likely to inherit a trait
+    pub fn trait_chance_bonus(&self) -> f64 {
+        match self {
+            Rarity::Common => 0.0,
+            Rarity::Rare => 0.15,
+            Rarity::Legendary => 0.3,
+        }
+    }
+
+    fn upgraded(&self) -> Self {
+        match self {
+            Rarity::Common => Rarity::Rare,
+            Rarity::Rare | Rarity::Legendary => Rarity::Legendary,
+        }
+    }
+}
+
+/// The colour genes of a sheep, they are passed on when merging and breeding
+#[derive(Clone, Debug)]
+pub struct Genome {
+    /// Black sheep are `0.1`, white sheep `1.0`
+    pub brightness: f32,
+    /// In degrees, the hue makes a sheep more likely to get a certain trait
+    pub hue: f32,
+    pub pattern: Pattern,
+    pub rarity: Rarity,
+}
+
+impl Default for Genome {
+    fn default() -> Self {
+        Self {
+            brightness: 1.0,
+            hue: 0.0,
+            pattern: Pattern::Plain,
+            rarity: Rarity::Common,
+        }
+    }
+}
+
+impl Genome {
+    pub fn random() -> Self {
+        let mut rng = thread_rng();
+
+        Self {
+            brightness: if rng.gen_range(0.0..=1.0) >= 0.2 {
+                // White sheep more likely than black sheep
+                rng.gen_range(0.8..=1.0)
+            } else {
+                rng.gen_range(0.1..=0.3)
+            },
+            hue: rng.gen_range(0.0..360.0),
+            pattern: match rng.gen_range(0.0..=1.0) {
+                chance if chance < 0.7 => Pattern::Plain,
+                chance if chance < 0.9 => Pattern::Spotted,
+                _ => Pattern::Striped,
+            },
+            rarity: match rng.gen_range(0.0..=1.0) {
+                chance if chance < 0.8 => Rarity::Common,
+                chance if chance < 0.97 => Rarity::Rare,
+                _ => Rarity::Legendary,
+            },
+        }
+    }
+
+    /// The genome of the offspring of two sheep
+    pub fn mix(&self, other: &Self) -> Self {
+        let mut rng = thread_rng();
+
+        // Average the hues on the colour wheel, so red and purple give a red and not a green sheep
+        let (sin, cos) = [self.hue, other.hue]
+            .iter()
+            .map(|hue| hue.to_radians().sin_cos())
+            .fold((0.0f32, 0.0f32), |(sin, cos), (hue_sin, hue_cos)| {
+                (sin + hue_sin, cos + hue_cos)
+            });
+        let hue = sin.atan2(cos).to_degrees() + rng.gen_range(-MAX_HUE_MUTATION..=MAX_HUE_MUTATION);
+
+        let rarity = self.rarity.max(other.rarity);
+
+        Self {
+            brightness: 0.1f32
+                .max((self.brightness + other.brightness) / 2.0 + rng.gen_range(-0.1..=0.1)),
+            hue: hue.rem_euclid(360.0),
+            pattern: *[self.pattern, other.pattern].choose(&mut rng).unwrap(),
+            rarity: match self.rarity == other.rarity && rng.gen_bool(RARITY_UPGRADE_CHANCE) {
+                true => rarity.upgraded(),
+                false => rarity,
+            },
+        }
+    }
+
+    /// Warm sheep make good tanks, green ones good spears and blue ones good medics
+    pub fn favoured_trait(&self) -> Trait {
+        match self.hue {
+            hue if hue < 90.0 || hue >= 300.0 => Trait::Tank,
+            hue if hue < 180.0 => Trait::Spear,
+            _ => Trait::Medic,
+        }
+    }
+
+    /// Common sheep are almost white, the rarer ones show their colours
+    pub fn color(&self) -> Color {
+        let saturation = match self.rarity {
+            Rarity::Common => 0.15,
+            Rarity::Rare => 0.4,
+            Rarity::Legendary => 0.7,
+        };
+        Color::hsl(self.hue, saturation, 0.85).as_rgba() * self.brightness
+    }
+
+    fn hue_label(&self) -> &'static str {
+        [
+            "red", "orange", "yellow", "green", "teal", "blue", "purple", "pink",
+        ][((self.hue + 22.5).rem_euclid(360.0) / 45.0) as usize % 8]
+    }
+
+    pub fn description(&self) -> String {
+        format!(
+            "{} {} {} sheep",
+            self.rarity.label(),
+            self.pattern.label(),
+            self.hue_label()
+        )
+    }
+}
@@ -15,13 +15,28 @@ use bevy_simple_stat_bars::prelude::*;
 use iyes_loopless::prelude::*;
 use utils::{despawn_entities_with_component, UnloadOnExit};
 
+mod actions;
 mod animation;
 mod audio;
 mod battle;
 mod battle_report;
+mod breeding;
+mod camera;
 mod debug;
+mod dialog;
 mod drag;
+mod economy;
+mod gamepad;
+mod genetics;
+mod naming;
+mod options;
+mod pen;
+mod pointer;
+mod roster;
+mod settings;
 mod sheep;
+mod stats;
+mod trait_pick;
 mod ui;
 mod utils;
 
@@ -33,6 +48,7 @@ const WINDOW_WIDTH: f32 = WINDOW_HEIGHT * RESOLUTION;
 pub enum GameState {
     MainMenu,
     Herding,
+    Deployment,
     Battle,
     BattleReport,
     Paused,
@@ -40,33 +56,6 @@ pub enum GameState {
 
 pub struct NewGame;
 
-trait ScreenToWorld {
-    fn screen_to_world(
-        &self,
-        windows: Res<Windows>,
-        camera: Query<(&Camera, &GlobalTransform)>,
-    ) -> Self;
-}
-
-impl ScreenToWorld for Vec2 {
-    // NOTE: if we end up using multiple screens, this will have to be adjusted
-    fn screen_to_world(
-        &self,
-        windows: Res<Windows>,
-        camera: Query<(&Camera, &GlobalTransform)>,
-    ) -> Self {
-        // Logic here courtesy of bevy cheat book
-        // https://bevy-cheatbook.github.io/cookbook/cursor2world.html
-        let window = windows.get_primary().unwrap();
-        let (camera, camera_transform) = camera.single();
-        let win_size = Vec2::new(window.width() as f32, window.height() as f32);
-        let ndc = (*self / win_size) * 2.0 - Vec2::ONE;
-        let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
-        let world_pos: Vec2 = ndc_to_world.project_point3(ndc.extend(-1.0)).truncate();
-        world_pos
-    }
-}
-
 fn main() {
     App::new()
         .insert_resource(ImageSettings::default_nearest())
@@ -74,13 +63,14 @@ fn main() {
             width: WINDOW_WIDTH,
             height: WINDOW_HEIGHT,
             title: "War Sheep".to_string(),
-            //resizable: false, // I am using tiling WM so this is just easier for time being, can
-            ..default() // adjust later
+            // The playfield is letterboxed by `camera::letterbox_camera`, so any size works
+            ..default()
         })
         .insert_resource(battle::Level(1))
         .insert_resource(NewGame)
         .add_loopless_state(GameState::Herding)
         .add_plugins(DefaultPlugins)
+        .add_plugin(actions::ActionsPlugin)
         .add_plugin(audio::AudioPlugin)
         .add_plugin(animation::AnimationPlugin)
         .add_plugin(battle::war_machines::WarMachinePlugin)
@@ -88,10 +78,25 @@ fn main() {
         .add_plugin(sheep::SheepPlugin)
         .add_plugin(drag::DragPlugin)
         .add_plugin(battle::BattlePlugin)
+        .add_plugin(battle::damage::DamagePlugin)
+        .add_plugin(battle::boss::BossPlugin)
+        .add_plugin(battle::status::StatusPlugin)
+        .add_plugin(battle::deployment::DeploymentPlugin)
         .add_plugin(battle_report::BattleReportPlugin)
         .add_plugin(ui::UiPlugin)
+        .add_plugin(options::OptionsPlugin)
+        .add_plugin(dialog::DialogPlugin)
+        .add_plugin(trait_pick::TraitPickPlugin)
+        .add_plugin(economy::EconomyPlugin)
+        .add_plugin(pen::PenPlugin)
+        .add_plugin(breeding::BreedingPlugin)
+        .add_plugin(naming::NamingPlugin)
+        .add_plugin(roster::RosterPlugin)
+        .add_plugin(stats::StatsPlugin)
         .add_plugin(StatBarsPlugin)
-        .add_startup_system(spawn_camera)
+        .add_plugin(camera::CameraPlugin)
+        .add_plugin(pointer::PointerPlugin)
+        .add_plugin(gamepad::GamepadPlugin)
         .add_enter_system(GameState::Herding, spawn_farm_scene)
         .add_exit_system(
             GameState::Herding,
@@ -134,13 +139,3 @@ fn spawn_farm_scene(mut commands: Commands, asset_server: Res<AssetServer>) {
         .insert(UnloadOnExit)
         .insert(Name::from("FarmFront"));
 }
-
-fn spawn_camera(mut commands: Commands) {
-    commands.spawn_bundle(Camera2dBundle {
-        projection: OrthographicProjection {
-            scale: 0.02,
-            ..default()
-        },
-        ..default()
-    });
-}
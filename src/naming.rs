@@ -0,0 +1,248 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+
+use crate::actions::{Action, Actions, InputMap, TextInput};
+use crate::dialog::{no_modal_open, ModalApp};
+use crate::drag::Drag;
+use crate::sheep::{Select, Sheep};
+use crate::ui::{
+    text_size, write_hud_text, write_text, Anchor, AsciiSheet, HudAnchor, LETTER_TILE_WIDTH,
+};
+use crate::utils::UnloadOnExit;
+use crate::GameState;
+
+const MAX_NAME_LENGTH: usize = 12;
+
+const NAME_STARTS: [&str; 12] = [
+    "Baa", "Dol", "Fluff", "Wool", "Lamb", "Shau", "Mut", "Ewe", "Tuft", "Curl", "Bram", "Mer",
+];
+const NAME_ENDS: [&str; 12] = [
+    "bara", "ly", "ton", "ie", "bert", "ina", "o", "sy", "wick", "ster", "ble", "n",
+];
+
+/// Names are made of two random syllables, like `Baabara` or `Fluffwick`
+pub fn random_name() -> String {
+    let mut rng = thread_rng();
+    format!(
+        "{}{}",
+        NAME_STARTS.choose(&mut rng).unwrap(),
+        NAME_ENDS.choose(&mut rng).unwrap()
+    )
+}
+
+/// If this resource is present, the player types a new name for the sheep
+pub struct RenameSheep {
+    sheep: Entity,
+    name: String,
+}
+
+/// Marker component for everything that is part of the rename prompt
+#[derive(Component)]
+struct RenameEntity;
+
+/// Marker component for the name above the selected sheep
+#[derive(Component)]
+struct SelectedName;
+
+pub struct NamingPlugin;
+
+impl Plugin for NamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_modal::<RenameSheep>()
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Herding)
+                    .with_system(show_selected_name)
+                    .with_system(start_rename.run_if(no_modal_open))
+                    .with_system(rename_input)
+                    .with_system(render_rename.run_if_resource_exists::<RenameSheep>())
+                    .into(),
+            )
+            .add_system(despawn_rename.run_unless_resource_exists::<RenameSheep>())
+            .add_exit_system(GameState::Herding, close_rename);
+    }
+}
+
+/// Keeps the name of the selected sheep above its head
+fn show_selected_name(
+    mut commands: Commands,
+    ascii_sheet: Res<AsciiSheet>,
+    select_q: Query<&Parent, With<Select>>,
+    sheep_q: Query<(&Sheep, &Transform)>,
+    mut label_q: Query<(Entity, &mut Transform), (With<SelectedName>, Without<Sheep>)>,
+    mut shown: Local<Option<(Entity, String)>>,
+) {
+    let selected = select_q
+        .iter()
+        .map(|parent| parent.get())
+        .find_map(|entity| sheep_q.get(entity).ok().map(|sheep| (entity, sheep)));
+
+    let (entity, (sheep, transform)) = match selected {
+        Some(selected) => selected,
+        None => {
+            label_q.for_each(|(label, _)| commands.entity(label).despawn_recursive());
+            *shown = None;
+            return;
+        }
+    };
+
+    let width = sheep.name().chars().count() as f32 * LETTER_TILE_WIDTH / 16.0;
+    let position = transform.translation.truncate() + Vec2::new(-width / 2.0, 0.9);
+
+    let current = (entity, sheep.name().to_string());
+    if shown.as_ref() == Some(&current) && !label_q.is_empty() {
+        for (_, mut label_transform) in label_q.iter_mut() {
+            label_transform.translation = position.extend(label_transform.translation.z);
+        }
+        return;
+    }
+
+    label_q.for_each(|(label, _)| commands.entity(label).despawn_recursive());
+
+    let label = write_text(
+        &mut commands,
+        &ascii_sheet,
+        position.extend(21.0),
+        Color::WHITE,
+        sheep.name(),
+    );
+    commands
+        .entity(label)
+        .insert(SelectedName)
+        .insert(UnloadOnExit);
+
+    *shown = Some(current);
+}
+
+fn start_rename(
+    mut commands: Commands,
+    actions: Actions,
+    select_q: Query<&Parent, With<Select>>,
+    sheep_q: Query<&Sheep>,
+    drag_q: Query<(), With<Drag>>,
+) {
+    // A sheep held while renaming could be merged away before the name is confirmed
+    if !actions.just_released(Action::Rename) || !drag_q.is_empty() {
+        return;
+    }
+
+    let selected = select_q
+        .iter()
+        .map(|parent| parent.get())
+        .find_map(|entity| sheep_q.get(entity).ok().map(|sheep| (entity, sheep)));
+
+    if let Some((sheep, sheep_component)) = selected {
+        commands.insert_resource(RenameSheep {
+            sheep,
+            name: sheep_component.name().to_string(),
+        });
+        commands.insert_resource(TextInput);
+    }
+}
+
+/// Reads the typed characters every frame, so nothing typed before is added to the name
+fn rename_input(
+    mut commands: Commands,
+    actions: Actions,
+    keys: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    rename: Option<ResMut<RenameSheep>>,
+    mut sheep_q: Query<(&mut Sheep, &mut Name)>,
+) {
+    let typed = characters
+        .iter()
+        .map(|character| character.char)
+        .collect::<String>();
+
+    let mut rename = match rename {
+        Some(rename) => rename,
+        None => return,
+    };
+
+    if actions.just_released(Action::Back) {
+        commands.remove_resource::<RenameSheep>();
+        commands.remove_resource::<TextInput>();
+        return;
+    }
+
+    if actions.just_released(Action::Confirm) {
+        let name = rename.name.trim();
+        if let Ok((mut sheep, mut sheep_name)) = sheep_q.get_mut(rename.sheep) {
+            if !name.is_empty() {
+                sheep.set_name(name.to_string());
+                sheep_name.set(name.to_string());
+            }
+        }
+        commands.remove_resource::<RenameSheep>();
+        commands.remove_resource::<TextInput>();
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        rename.name.pop();
+    }
+
+    for character in typed.chars() {
+        if (character.is_ascii_alphanumeric() || character == ' ' || character == '-')
+            && rename.name.chars().count() < MAX_NAME_LENGTH
+        {
+            rename.name.push(character);
+        }
+    }
+}
+
+fn render_rename(
+    mut commands: Commands,
+    rename: Res<RenameSheep>,
+    input_map: Res<InputMap>,
+    ascii_sheet: Res<AsciiSheet>,
+    rename_q: Query<Entity, With<RenameEntity>>,
+) {
+    if !rename.is_changed() {
+        return;
+    }
+
+    rename_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let text = format!(
+        "     Name your sheep\n\n{:^25}\n\n{} done   {} cancel",
+        format!("{}_", rename.name),
+        input_map.label(Action::Confirm),
+        input_map.label(Action::Back),
+    );
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.0, 0.0, 0.0, 0.85),
+                custom_size: Some(text_size(&text) + Vec2::splat(1.5)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudAnchor::new(Anchor::Center, Vec2::ZERO, 150.0))
+        .insert(RenameEntity)
+        .insert(Name::from("RenameBackground"));
+
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Center,
+        Vec2::ZERO,
+        160.0,
+        Color::WHITE,
+        &text,
+    );
+    commands.entity(text).insert(RenameEntity);
+}
+
+fn close_rename(mut commands: Commands) {
+    commands.remove_resource::<RenameSheep>();
+    commands.remove_resource::<TextInput>();
+}
+
+fn despawn_rename(mut commands: Commands, rename_q: Query<Entity, With<RenameEntity>>) {
+    rename_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
@@ -0,0 +1,272 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::actions::{Action, Actions, InputMap};
+use crate::audio::AudioSettings;
+use crate::dialog::{no_modal_open, ModalApp};
+use crate::ui::{text_size, write_hud_text, Anchor, AsciiSheet, HudAnchor};
+
+const VOLUME_STEP: f32 = 0.1;
+const VOLUME_BAR_LENGTH: usize = 10;
+
+/// If this resource is present, the options screen is shown on top of the current state
+pub struct OptionsMenu {
+    selected: usize,
+    /// If set, the next released key or button is bound to this action
+    rebinding: Option<Action>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum OptionsEntry {
+    Music,
+    Effects,
+    Binding(Action),
+}
+
+impl OptionsEntry {
+    fn all() -> Vec<OptionsEntry> {
+        [OptionsEntry::Music, OptionsEntry::Effects]
+            .into_iter()
+            .chain(Action::REBINDABLE.into_iter().map(OptionsEntry::Binding))
+            .collect()
+    }
+}
+
+impl OptionsMenu {
+    fn selected(&self) -> OptionsEntry {
+        OptionsEntry::all()[self.selected]
+    }
+}
+
+/// Marker component for everything that is part of the options screen
+#[derive(Component)]
+struct OptionsMenuEntity;
+
+pub struct OptionsPlugin;
+
+impl Plugin for OptionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_modal::<OptionsMenu>()
+            .add_system_set(
+                ConditionSet::new()
+                    .run_if(no_modal_open)
+                    .with_system(open_options_menu)
+                    .with_system(audio_hotkeys)
+                    .into(),
+            )
+            .add_system(despawn_options_menu.run_unless_resource_exists::<OptionsMenu>())
+            .add_system_set(
+                ConditionSet::new()
+                    .run_if_resource_exists::<OptionsMenu>()
+                    .with_system(options_menu_input)
+                    .with_system(render_options_menu)
+                    .into(),
+            );
+    }
+}
+
+fn open_options_menu(mut commands: Commands, actions: Actions) {
+    if actions.just_released(Action::Options) {
+        commands.insert_resource(OptionsMenu {
+            selected: 0,
+            rebinding: None,
+        });
+    }
+}
+
+/// Hold the `AdjustEffects` action to control the effects instead of the music
+fn audio_hotkeys(actions: Actions, mut audio_settings: ResMut<AudioSettings>) {
+    let entry = match actions.pressed(Action::AdjustEffects) {
+        true => OptionsEntry::Effects,
+        false => OptionsEntry::Music,
+    };
+
+    if actions.just_released(Action::Mute) {
+        toggle_mute(&mut audio_settings, entry);
+    }
+    if actions.just_released(Action::VolumeDown) {
+        change_volume(&mut audio_settings, entry, -VOLUME_STEP);
+    }
+    if actions.just_released(Action::VolumeUp) {
+        change_volume(&mut audio_settings, entry, VOLUME_STEP);
+    }
+}
+
+fn options_menu_input(
+    mut commands: Commands,
+    actions: Actions,
+    mut input_map: ResMut<InputMap>,
+    mut options_menu: ResMut<OptionsMenu>,
+    mut audio_settings: ResMut<AudioSettings>,
+) {
+    if let Some(action) = options_menu.rebinding {
+        if let Some(binding) = actions.released_binding() {
+            input_map.rebind(action, binding);
+            options_menu.rebinding = None;
+        }
+        return;
+    }
+
+    if actions.any_just_released([Action::Options, Action::Back]) {
+        commands.remove_resource::<OptionsMenu>();
+        return;
+    }
+
+    let count = OptionsEntry::all().len();
+    if actions.just_released(Action::MenuUp) {
+        options_menu.selected = (options_menu.selected + count - 1) % count;
+    }
+    if actions.just_released(Action::MenuDown) {
+        options_menu.selected = (options_menu.selected + 1) % count;
+    }
+
+    match options_menu.selected() {
+        OptionsEntry::Binding(action) => {
+            if actions.just_released(Action::Confirm) {
+                options_menu.rebinding = Some(action);
+            }
+        }
+        selected => {
+            if actions.just_released(Action::MenuLeft) {
+                change_volume(&mut audio_settings, selected, -VOLUME_STEP);
+            }
+            if actions.just_released(Action::MenuRight) {
+                change_volume(&mut audio_settings, selected, VOLUME_STEP);
+            }
+            if actions.just_released(Action::Mute) {
+                toggle_mute(&mut audio_settings, selected);
+            }
+        }
+    }
+}
+
+fn change_volume(audio_settings: &mut AudioSettings, entry: OptionsEntry, change: f32) {
+    let volume = match entry {
+        OptionsEntry::Music => &mut audio_settings.music_volume,
+        OptionsEntry::Effects => &mut audio_settings.effects_volume,
+        OptionsEntry::Binding(_) => return,
+    };
+    // Round to the step, so repeated changes don't accumulate float errors
+    *volume = ((*volume + change) / VOLUME_STEP)
+        .round()
+        .clamp(0.0, 1.0 / VOLUME_STEP)
+        * VOLUME_STEP;
+}
+
+fn toggle_mute(audio_settings: &mut AudioSettings, entry: OptionsEntry) {
+    match entry {
+        OptionsEntry::Music => audio_settings.music_muted = !audio_settings.music_muted,
+        OptionsEntry::Effects => audio_settings.effects_muted = !audio_settings.effects_muted,
+        OptionsEntry::Binding(_) => (),
+    }
+}
+
+fn volume_line(name: &str, volume: f32, muted: bool, selected: bool) -> String {
+    let filled = (volume * VOLUME_BAR_LENGTH as f32).round() as usize;
+    format!(
+        "{} {name:<8}[{}{}] {:>3}%{}",
+        if selected { ">" } else { " " },
+        "#".repeat(filled),
+        "-".repeat(VOLUME_BAR_LENGTH - filled),
+        (volume * 100.0).round(),
+        if muted { " muted" } else { "" }
+    )
+}
+
+fn binding_line(action: Action, input_map: &InputMap, selected: bool, rebinding: bool) -> String {
+    let bindings = match rebinding {
+        true => "press a key or button".to_string(),
+        false => input_map
+            .bindings(action)
+            .iter()
+            .map(|binding| binding.label())
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    format!(
+        "{} {:<9}{bindings}",
+        if selected { ">" } else { " " },
+        action.label()
+    )
+}
+
+/// Redraws the options screen whenever it, the settings or the bindings change
+fn render_options_menu(
+    mut commands: Commands,
+    options_menu: Res<OptionsMenu>,
+    audio_settings: Res<AudioSettings>,
+    input_map: Res<InputMap>,
+    ascii_sheet: Res<AsciiSheet>,
+    menu_q: Query<Entity, With<OptionsMenuEntity>>,
+) {
+    if !options_menu.is_changed() && !audio_settings.is_changed() && !input_map.is_changed() {
+        return;
+    }
+
+    menu_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let selected = options_menu.selected();
+    let bindings = Action::REBINDABLE
+        .iter()
+        .map(|action| {
+            binding_line(
+                *action,
+                &input_map,
+                selected == OptionsEntry::Binding(*action),
+                options_menu.rebinding == Some(*action),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let text = format!(
+        "             OPTIONS\n\n{}\n{}\n\n{bindings}\n\n\n{}/{} select   {}/{} volume\n\n{} rebind   {} mute   {} close",
+        volume_line(
+            "Music",
+            audio_settings.music_volume,
+            audio_settings.music_muted,
+            selected == OptionsEntry::Music
+        ),
+        volume_line(
+            "Effects",
+            audio_settings.effects_volume,
+            audio_settings.effects_muted,
+            selected == OptionsEntry::Effects
+        ),
+        input_map.label(Action::MenuUp),
+        input_map.label(Action::MenuDown),
+        input_map.label(Action::MenuLeft),
+        input_map.label(Action::MenuRight),
+        input_map.label(Action::Confirm),
+        input_map.label(Action::Mute),
+        input_map.label(Action::Options),
+    );
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.0, 0.0, 0.0, 0.85),
+                custom_size: Some(text_size(&text) + Vec2::splat(1.5)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudAnchor::new(Anchor::Center, Vec2::ZERO, 150.0))
+        .insert(OptionsMenuEntity)
+        .insert(Name::from("OptionsMenuBackground"));
+
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Center,
+        Vec2::ZERO,
+        160.0,
+        Color::WHITE,
+        &text,
+    );
+    commands.entity(text).insert(OptionsMenuEntity);
+}
+
+fn despawn_options_menu(mut commands: Commands, menu_q: Query<Entity, With<OptionsMenuEntity>>) {
+    menu_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::sheep::Sheep;
+use crate::ui::{write_hud_text, Anchor, AsciiSheet};
+use crate::utils::{Bounds, UnloadOnExit};
+use crate::GameState;
+
+/// The pen at its biggest, it starts smaller and grows with the upgrades
+pub const MAX_PEN_BOUNDS_X: Vec2 = Vec2::new(-6.2, 6.2);
+pub const MAX_PEN_BOUNDS_Y: Vec2 = Vec2::new(-6.4, 7.0);
+
+/// Every upgrade makes the pen this much bigger on each side
+const PEN_GROWTH: Vec2 = Vec2::new(1.5, 1.6);
+/// How many times the pen can be upgraded, until it reaches `MAX_PEN_BOUNDS_X/Y`
+pub const MAX_PEN_SIZE: usize = 2;
+
+const BASE_CAPACITY: usize = 12;
+const CAPACITY_PER_PEN_SIZE: usize = 6;
+
+/// Upgrades bought in the shop, they are kept until a new game is started
+#[derive(Default)]
+pub struct PenUpgrades {
+    pub shears: usize,
+    pub hay: usize,
+    pub size: usize,
+}
+
+impl PenUpgrades {
+    pub fn pen_bounds(&self) -> Bounds {
+        let shrink = PEN_GROWTH * (MAX_PEN_SIZE - self.size.min(MAX_PEN_SIZE)) as f32;
+        Bounds {
+            x: (MAX_PEN_BOUNDS_X.x + shrink.x, MAX_PEN_BOUNDS_X.y - shrink.x),
+            y: (MAX_PEN_BOUNDS_Y.x + shrink.y, MAX_PEN_BOUNDS_Y.y - shrink.y),
+        }
+    }
+
+    /// How many sheep fit into the pen
+    pub fn capacity(&self) -> usize {
+        BASE_CAPACITY + CAPACITY_PER_PEN_SIZE * self.size
+    }
+}
+
+/// If this resource is present, there are `.0` sheep too many in the pen. They have to be sold or
+/// merged before the next battle.
+pub struct Overcrowded(pub usize);
+
+/// Marker component for the fenced area of the pen
+#[derive(Component)]
+struct PenSprite;
+
+/// Marker component for the sheep count text
+#[derive(Component)]
+struct PenText;
+
+pub struct PenPlugin;
+
+impl Plugin for PenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PenUpgrades>()
+            .add_enter_system_set(
+                GameState::Herding,
+                ConditionSet::new()
+                    .with_system(spawn_pen)
+                    .with_system(return_sheep_to_pen)
+                    .into(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Herding)
+                    .with_system(fit_sheep_into_pen)
+                    .with_system(resize_pen)
+                    .with_system(check_capacity)
+                    .with_system(update_pen_text)
+                    .into(),
+            )
+            .add_exit_system(GameState::Herding, forget_overcrowding);
+    }
+}
+
+fn spawn_pen(mut commands: Commands) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.5, 0.8, 0.3, 0.25),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec2::ZERO.extend(1.0)),
+            ..default()
+        })
+        .insert(PenSprite)
+        .insert(UnloadOnExit)
+        .insert(Name::from("Pen"));
+}
+
+/// The sheep come back from the battlefield, keep them inside of the pen again
+fn return_sheep_to_pen(upgrades: Res<PenUpgrades>, mut sheep_q: Query<&mut Bounds, With<Sheep>>) {
+    for mut bounds in sheep_q.iter_mut() {
+        *bounds = upgrades.pen_bounds();
+    }
+}
+
+/// New sheep have to stay in the pen, and all of them get more room once the pen grows
+fn fit_sheep_into_pen(
+    upgrades: Res<PenUpgrades>,
+    mut sheep_q: Query<(&mut Bounds, ChangeTrackers<Sheep>)>,
+) {
+    for (mut bounds, sheep) in sheep_q.iter_mut() {
+        if upgrades.is_changed() || sheep.is_added() {
+            *bounds = upgrades.pen_bounds();
+        }
+    }
+}
+
+fn resize_pen(
+    upgrades: Res<PenUpgrades>,
+    mut pen_q: Query<(&mut Sprite, &mut Transform), With<PenSprite>>,
+    added_q: Query<(), Added<PenSprite>>,
+) {
+    if !upgrades.is_changed() && added_q.is_empty() {
+        return;
+    }
+
+    let bounds = upgrades.pen_bounds();
+    // The bounds keep the center of the sheep inside, so the fence is a bit further out
+    let margin = Vec2::splat(1.0);
+    let min = Vec2::new(bounds.x.0, bounds.y.0) - margin / 2.0;
+    let max = Vec2::new(bounds.x.1, bounds.y.1) + margin / 2.0;
+
+    for (mut sprite, mut transform) in pen_q.iter_mut() {
+        sprite.custom_size = Some(max - min);
+        transform.translation = ((min + max) / 2.0).extend(transform.translation.z);
+    }
+}
+
+fn check_capacity(
+    mut commands: Commands,
+    upgrades: Res<PenUpgrades>,
+    overcrowded: Option<Res<Overcrowded>>,
+    sheep_q: Query<(), With<Sheep>>,
+) {
+    let count = sheep_q.iter().count();
+    let capacity = upgrades.capacity();
+
+    if count > capacity {
+        if overcrowded.map(|overcrowded| overcrowded.0) != Some(count - capacity) {
+            commands.insert_resource(Overcrowded(count - capacity));
+        }
+    } else if overcrowded.is_some() {
+        commands.remove_resource::<Overcrowded>();
+    }
+}
+
+fn update_pen_text(
+    mut commands: Commands,
+    ascii_sheet: Res<AsciiSheet>,
+    upgrades: Res<PenUpgrades>,
+    sheep_q: Query<(), With<Sheep>>,
+    text_q: Query<Entity, With<PenText>>,
+    mut shown: Local<Option<(usize, usize)>>,
+) {
+    let size = (sheep_q.iter().count(), upgrades.capacity());
+    if *shown == Some(size) && !text_q.is_empty() {
+        return;
+    }
+    *shown = Some(size);
+
+    text_q.for_each(|text| commands.entity(text).despawn_recursive());
+
+    let (count, capacity) = size;
+    let mut texts = vec![write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::TopRight,
+        Vec2::new(-0.5, -2.5),
+        50.0,
+        match count > capacity {
+            true => Color::ORANGE_RED,
+            false => Color::WHITE,
+        },
+        format!("Pen: {count}/{capacity}").as_str(),
+    )];
+
+    if count > capacity {
+        texts.push(write_hud_text(
+            &mut commands,
+            &ascii_sheet,
+            Anchor::Bottom,
+            Vec2::new(0.0, 1.5),
+            50.0,
+            Color::ORANGE_RED,
+            format!("The pen is full! Sell or merge {} sheep", count - capacity).as_str(),
+        ));
+    }
+
+    for text in texts {
+        commands.entity(text).insert(PenText).insert(UnloadOnExit);
+    }
+}
+
+fn forget_overcrowding(mut commands: Commands) {
+    commands.remove_resource::<Overcrowded>();
+}
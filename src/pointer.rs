@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::actions::{Action, Actions};
+use crate::camera::{window_to_world, CameraZoom, CursorWorld, MainCamera};
+use crate::dialog::no_modal_open;
+use crate::gamepad::GamepadCursor;
+use crate::GameState;
+
+/// Mouse, touch and gamepad input behind a single pointer, so the game doesn't have to care which
+/// one is used. A single touch acts like the left mouse button, two fingers pan and zoom the
+/// camera. The `Grab` action presses the pointer at the gamepad cursor.
+#[derive(Default)]
+pub struct Pointer {
+    /// World position of the pointer
+    pub position: Option<Vec2>,
+    pressed: bool,
+    just_pressed: bool,
+    just_released: bool,
+    /// What is holding the pointer down, the mouse if `None`
+    held_by: Option<PointerSource>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum PointerSource {
+    Touch(u64),
+    Gamepad,
+}
+
+impl Pointer {
+    pub fn pressed(&self) -> bool {
+        self.pressed
+    }
+
+    pub fn just_pressed(&self) -> bool {
+        self.just_pressed
+    }
+
+    pub fn just_released(&self) -> bool {
+        self.just_released
+    }
+
+    fn press(&mut self, held_by: Option<PointerSource>) {
+        self.pressed = true;
+        self.just_pressed = true;
+        self.held_by = held_by;
+    }
+
+    fn release(&mut self) {
+        if self.pressed {
+            self.pressed = false;
+            self.just_released = true;
+        }
+        self.held_by = None;
+    }
+}
+
+pub struct PointerPlugin;
+
+impl Plugin for PointerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pointer>().add_system_to_stage(
+            CoreStage::First,
+            update_pointer.label("pointer").after("cursor_world"),
+        );
+
+        // The camera only moves on the playfield, not under a menu or the battle report
+        for state in [GameState::Herding, GameState::Deployment, GameState::Battle] {
+            app.add_system(pinch_and_pan.run_in_state(state).run_if(no_modal_open));
+        }
+    }
+}
+
+fn update_pointer(
+    mut pointer: ResMut<Pointer>,
+    mouse_btn: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    cursor_world: Res<CursorWorld>,
+    actions: Actions,
+    gamepad_cursor: Res<GamepadCursor>,
+    windows: Res<Windows>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+) {
+    pointer.just_pressed = false;
+    pointer.just_released = false;
+
+    // Touches are only reported for the primary window
+    let touch_to_world = |touch: &Touch| {
+        windows
+            .get_primary()
+            .and_then(|window| window_to_world(window, touch.position(), &camera_q))
+    };
+
+    // Two fingers are a camera gesture, drop whatever the pointer was holding
+    if touches.iter().count() >= 2 {
+        pointer.release();
+        return;
+    }
+
+    if !pointer.pressed {
+        if let Some(touch) = touches.iter_just_pressed().next() {
+            pointer.position = touch_to_world(touch);
+            pointer.press(Some(PointerSource::Touch(touch.id())));
+            return;
+        }
+
+        if actions.just_pressed(Action::Grab) {
+            pointer.position = gamepad_cursor.position;
+            pointer.press(Some(PointerSource::Gamepad));
+            return;
+        }
+    }
+
+    match pointer.held_by {
+        Some(PointerSource::Touch(id)) => match touches.get_pressed(id) {
+            Some(touch) => pointer.position = touch_to_world(touch),
+            None => pointer.release(),
+        },
+        Some(PointerSource::Gamepad) => {
+            pointer.position = gamepad_cursor.position;
+            if actions.just_released(Action::Grab) {
+                pointer.release();
+            }
+        }
+        None => {
+            pointer.position = cursor_world.position;
+            if mouse_btn.just_pressed(MouseButton::Left) {
+                pointer.press(None);
+            } else if mouse_btn.just_released(MouseButton::Left) {
+                pointer.release();
+            }
+        }
+    }
+}
+
+/// Pinching with two fingers zooms the camera, moving them pans it
+fn pinch_and_pan(
+    touches: Res<Touches>,
+    mut camera_q: Query<
+        (&mut Transform, &mut CameraZoom, &OrthographicProjection),
+        With<MainCamera>,
+    >,
+) {
+    let fingers = touches.iter().take(2).collect::<Vec<_>>();
+    if fingers.len() < 2 {
+        return;
+    }
+
+    let (mut transform, mut zoom, projection) = match camera_q.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    let distance = fingers[0].position().distance(fingers[1].position());
+    let previous_distance = fingers[0]
+        .previous_position()
+        .distance(fingers[1].previous_position());
+    if previous_distance > 0.0 {
+        zoom.0 *= distance / previous_distance;
+    }
+
+    // Move the camera against the fingers, so the playfield follows them
+    let midpoint = (fingers[0].position() + fingers[1].position()) / 2.0;
+    let previous_midpoint = (fingers[0].previous_position() + fingers[1].previous_position()) / 2.0;
+    transform.translation -= ((midpoint - previous_midpoint) * projection.scale).extend(0.0);
+}
@@ -0,0 +1,422 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::actions::{Action, Actions, InputMap};
+use crate::dialog::{no_modal_open, only_modal_open, ModalApp};
+use crate::pointer::Pointer;
+use crate::sheep::{Sheep, Trait};
+use crate::ui::{text_size, write_hud_text, Anchor, AsciiSheet, HudAnchor, LETTER_TILE_WIDTH};
+use crate::utils::{Attack, Health, Speed, UnloadOnExit};
+use crate::GameState;
+
+/// How many sheep are listed at once, the list scrolls with the selection
+const VISIBLE_SHEEP: usize = 12;
+/// Line of the text the sort entry is on, the filter is on the next one
+const SORT_LINE: usize = 2;
+/// Line of the text the first listed sheep is on
+const FIRST_SHEEP_LINE: usize = 6;
+/// The sort and filter entries come before the sheep
+const SETTING_ENTRIES: usize = 2;
+
+const HIGHLIGHT_SECS: f32 = 3.0;
+const HIGHLIGHT_BLINK_SECS: f32 = 0.25;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum RosterSort {
+    Level,
+    Rank,
+    Health,
+    Kills,
+    Name,
+}
+
+impl RosterSort {
+    const ALL: [RosterSort; 5] = [
+        RosterSort::Level,
+        RosterSort::Rank,
+        RosterSort::Health,
+        RosterSort::Kills,
+        RosterSort::Name,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            RosterSort::Level => "level",
+            RosterSort::Rank => "rank",
+            RosterSort::Health => "wounded first",
+            RosterSort::Kills => "kills",
+            RosterSort::Name => "name",
+        }
+    }
+
+    fn compare(&self, a: &RosterRow, b: &RosterRow) -> Ordering {
+        match self {
+            RosterSort::Level => b.sheep.sum_levels().total_cmp(&a.sheep.sum_levels()),
+            RosterSort::Rank => b.sheep.rank().cmp(&a.sheep.rank()),
+            RosterSort::Health => a.health.ratio().total_cmp(&b.health.ratio()),
+            RosterSort::Kills => b.sheep.kills().cmp(&a.sheep.kills()),
+            RosterSort::Name => a.sheep.name().cmp(b.sheep.name()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum RosterFilter {
+    All,
+    Trait(Trait),
+    Wounded,
+}
+
+impl RosterFilter {
+    fn all() -> Vec<RosterFilter> {
+        std::iter::once(RosterFilter::All)
+            .chain(Trait::ALL.into_iter().map(RosterFilter::Trait))
+            .chain([RosterFilter::Wounded])
+            .collect()
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            RosterFilter::All => "all",
+            RosterFilter::Trait(filtered) => filtered.label(),
+            RosterFilter::Wounded => "wounded",
+        }
+    }
+
+    fn matches(&self, row: &RosterRow) -> bool {
+        match self {
+            RosterFilter::All => true,
+            RosterFilter::Trait(filtered) => row.sheep.has_trait(*filtered),
+            RosterFilter::Wounded => row.health.current < row.health.max,
+        }
+    }
+}
+
+/// If this resource is present, the roster lists all the sheep in the pen and everything else in
+/// the pen ignores the input
+pub struct Roster {
+    /// The sort and filter entries, followed by the listed sheep
+    selected: usize,
+    sort: usize,
+    filter: usize,
+}
+
+impl Roster {
+    fn sort(&self) -> RosterSort {
+        RosterSort::ALL[self.sort]
+    }
+
+    fn filter(&self) -> RosterFilter {
+        RosterFilter::all()[self.filter]
+    }
+
+    /// Index of the first listed sheep, so the selected one is always visible
+    fn first_visible(&self, count: usize) -> usize {
+        let selected = self.selected.saturating_sub(SETTING_ENTRIES);
+        selected
+            .saturating_sub(VISIBLE_SHEEP - 1)
+            .min(count.saturating_sub(VISIBLE_SHEEP))
+    }
+}
+
+struct RosterRow<'a> {
+    entity: Entity,
+    sheep: &'a Sheep,
+    attack: &'a Attack,
+    speed: &'a Speed,
+    health: &'a Health,
+}
+
+impl<'a> RosterRow<'a> {
+    fn line(&self, selected: bool) -> String {
+        format!(
+            "{} {:<12} {:>3} {:>2} {:<11} {:>4.1} {:>4.1} {:>3.0}/{:<3.0} {:>2} {:>3}",
+            if selected { ">" } else { " " },
+            self.sheep.name(),
+            self.sheep.sum_levels(),
+            self.sheep.rank(),
+            self.sheep.levels_label(),
+            self.attack.attack_damage,
+            self.speed.0,
+            self.health.current.max(0.0),
+            self.health.max,
+            self.sheep.battles(),
+            self.sheep.kills(),
+        )
+    }
+}
+
+/// The listed sheep, filtered and sorted like the roster says
+fn roster_rows<'a>(
+    roster: &Roster,
+    sheep_q: &'a Query<(Entity, &Sheep, &Attack, &Speed, &Health)>,
+) -> Vec<RosterRow<'a>> {
+    let filter = roster.filter();
+    let sort = roster.sort();
+
+    let mut rows = sheep_q
+        .iter()
+        .map(|(entity, sheep, attack, speed, health)| RosterRow {
+            entity,
+            sheep,
+            attack,
+            speed,
+            health,
+        })
+        .filter(|row| filter.matches(row))
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| sort.compare(a, b));
+    rows
+}
+
+/// Marker component for everything that is part of the roster
+#[derive(Component)]
+struct RosterEntity;
+
+/// Marker component for the text of the roster, to find the clicked line
+#[derive(Component)]
+struct RosterText;
+
+/// Blinking box around the sheep picked in the roster
+#[derive(Component)]
+struct RosterHighlight(Timer);
+
+pub struct RosterPlugin;
+
+impl Plugin for RosterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_modal::<Roster>()
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Herding)
+                    .with_system(open_roster.run_if(no_modal_open))
+                    .with_system(roster_input.run_if(only_modal_open::<Roster>))
+                    .into(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Herding)
+                    .with_system(render_roster.run_if_resource_exists::<Roster>())
+                    .with_system(blink_highlight)
+                    .into(),
+            )
+            .add_system(despawn_roster.run_unless_resource_exists::<Roster>())
+            .add_exit_system(GameState::Herding, close_roster);
+    }
+}
+
+fn open_roster(mut commands: Commands, actions: Actions) {
+    if actions.just_released(Action::Roster) {
+        commands.insert_resource(Roster {
+            selected: SETTING_ENTRIES,
+            sort: 0,
+            filter: 0,
+        });
+    }
+}
+
+fn roster_input(
+    mut commands: Commands,
+    actions: Actions,
+    pointer: Res<Pointer>,
+    assets: Res<AssetServer>,
+    mut roster: ResMut<Roster>,
+    sheep_q: Query<(Entity, &Sheep, &Attack, &Speed, &Health)>,
+    text_q: Query<&GlobalTransform, With<RosterText>>,
+) {
+    if actions.just_released(Action::Roster) || actions.just_released(Action::Back) {
+        commands.remove_resource::<Roster>();
+        return;
+    }
+
+    let rows = roster_rows(&roster, &sheep_q);
+    let entries = SETTING_ENTRIES + rows.len();
+
+    // Clicking a line selects it, like moving there and confirming
+    let mut clicked = false;
+    if let (true, Some(position), Ok(text)) = (
+        pointer.just_pressed(),
+        pointer.position,
+        text_q.get_single(),
+    ) {
+        let (scale, _, translation) = text.to_scale_rotation_translation();
+        let tile = LETTER_TILE_WIDTH / 16.0 * scale.y;
+        let line = ((translation.y - position.y) / tile).round();
+        if line >= 0.0 && position.x >= translation.x - tile / 2.0 {
+            let line = line as usize;
+            let first = roster.first_visible(rows.len());
+            let entry = match line {
+                line if (SORT_LINE..SORT_LINE + SETTING_ENTRIES).contains(&line) => {
+                    Some(line - SORT_LINE)
+                }
+                line if line >= FIRST_SHEEP_LINE => {
+                    Some(SETTING_ENTRIES + first + line - FIRST_SHEEP_LINE)
+                        .filter(|entry| *entry < entries && line < FIRST_SHEEP_LINE + VISIBLE_SHEEP)
+                }
+                _ => None,
+            };
+            if let Some(entry) = entry {
+                roster.selected = entry;
+                clicked = true;
+            }
+        }
+    }
+
+    if actions.just_released(Action::MenuUp) {
+        roster.selected = (roster.selected + entries - 1) % entries;
+    } else if actions.just_released(Action::MenuDown) {
+        roster.selected = (roster.selected + 1) % entries;
+    } else if roster.selected >= entries {
+        // Sheep were filtered out or left the pen
+        roster.selected = entries - 1;
+    }
+
+    let step = |value: usize, count: usize| {
+        if actions.just_released(Action::MenuLeft) {
+            (value + count - 1) % count
+        } else if actions.just_released(Action::MenuRight) || clicked {
+            (value + 1) % count
+        } else {
+            value
+        }
+    };
+    match roster.selected {
+        0 => roster.sort = step(roster.sort, RosterSort::ALL.len()),
+        1 => roster.filter = step(roster.filter, RosterFilter::all().len()),
+        selected if clicked || actions.just_released(Action::Confirm) => {
+            let sheep = rows[selected - SETTING_ENTRIES].entity;
+            highlight_sheep(&mut commands, &assets, sheep);
+            commands.remove_resource::<Roster>();
+        }
+        _ => {}
+    }
+}
+
+fn highlight_sheep(commands: &mut Commands, assets: &AssetServer, sheep: Entity) {
+    let highlight = commands
+        .spawn_bundle(SpriteBundle {
+            texture: assets.load("OutlineBox.png"),
+            sprite: Sprite {
+                color: Color::GOLD,
+                custom_size: Some(Vec2::splat(24.0) / 16.0),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec2::ZERO.extend(31.0)),
+            ..default()
+        })
+        .insert(RosterHighlight(Timer::from_seconds(HIGHLIGHT_SECS, false)))
+        .insert(UnloadOnExit)
+        .insert(Name::from("RosterHighlight"))
+        .id();
+    commands.entity(sheep).add_child(highlight);
+}
+
+fn blink_highlight(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut highlight_q: Query<(Entity, &mut RosterHighlight, &mut Visibility)>,
+) {
+    for (entity, mut highlight, mut visibility) in highlight_q.iter_mut() {
+        if highlight.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        visibility.is_visible =
+            (highlight.0.elapsed_secs() / HIGHLIGHT_BLINK_SECS) as usize % 2 == 0;
+    }
+}
+
+/// Redraws the roster every frame, the health and stats of the sheep change all the time
+fn render_roster(
+    mut commands: Commands,
+    roster: Res<Roster>,
+    input_map: Res<InputMap>,
+    ascii_sheet: Res<AsciiSheet>,
+    sheep_q: Query<(Entity, &Sheep, &Attack, &Speed, &Health)>,
+    roster_q: Query<Entity, With<RosterEntity>>,
+    mut shown: Local<Option<String>>,
+) {
+    let rows = roster_rows(&roster, &sheep_q);
+    let first = roster.first_visible(rows.len());
+    let mut lines = rows
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(VISIBLE_SHEEP)
+        .map(|(i, row)| row.line(roster.selected == SETTING_ENTRIES + i))
+        .collect::<Vec<_>>();
+    lines.resize(VISIBLE_SHEEP, String::new());
+    if rows.is_empty() {
+        lines[0] = "  No sheep".to_string();
+    }
+
+    let selected = |entry: usize| if roster.selected == entry { ">" } else { " " };
+    let text = format!(
+        "{:^60}\n\n{} Sort:   < {} >\n{} Filter: < {} >\n\n  {:<12} {:>3} {:>2} {:<11} {:>4} {:>4} {:>7} {:>2} {:>3}\n{}\n\n{}/{} select   {}/{} change   {} show in pen   {} close",
+        format!("ROSTER ({} of {} sheep)", rows.len(), sheep_q.iter().count()),
+        selected(0),
+        roster.sort().label(),
+        selected(1),
+        roster.filter().label(),
+        "Name",
+        "Lvl",
+        "Rk",
+        "Traits",
+        "Atk",
+        "Spd",
+        "HP",
+        "Bt",
+        "Kil",
+        lines.join("\n"),
+        input_map.label(Action::MenuUp),
+        input_map.label(Action::MenuDown),
+        input_map.label(Action::MenuLeft),
+        input_map.label(Action::MenuRight),
+        input_map.label(Action::Confirm),
+        input_map.label(Action::Roster),
+    );
+    if shown.as_ref() == Some(&text) && !roster_q.is_empty() {
+        return;
+    }
+
+    roster_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.0, 0.0, 0.0, 0.85),
+                custom_size: Some(text_size(&text) + Vec2::splat(1.5)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudAnchor::new(Anchor::Center, Vec2::ZERO, 150.0))
+        .insert(RosterEntity)
+        .insert(Name::from("RosterBackground"));
+
+    let text_entity = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Center,
+        Vec2::ZERO,
+        160.0,
+        Color::WHITE,
+        &text,
+    );
+    commands
+        .entity(text_entity)
+        .insert(RosterEntity)
+        .insert(RosterText);
+
+    *shown = Some(text);
+}
+
+fn close_roster(mut commands: Commands) {
+    commands.remove_resource::<Roster>();
+}
+
+fn despawn_roster(mut commands: Commands, roster_q: Query<Entity, With<RosterEntity>>) {
+    roster_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
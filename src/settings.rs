@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// Settings are stored as `key=value` lines, one file (or local storage entry on the web) per
+/// settings group.
+#[cfg(not(target_arch = "wasm32"))]
+const SETTINGS_DIR: &str = "settings";
+
+#[cfg(target_arch = "wasm32")]
+const STORAGE_PREFIX: &str = "war-sheep";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn settings_path(group: &str) -> std::path::PathBuf {
+    std::path::Path::new(SETTINGS_DIR).join(format!("{group}.txt"))
+}
+
+/// Returns the stored settings of the `group`, if there are any
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(group: &str) -> Option<String> {
+    std::fs::read_to_string(settings_path(group)).ok()
+}
+
+/// Stores the settings of the `group`, failing to do so is not fatal
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(group: &str, contents: &str) {
+    let path = settings_path(group);
+    if let Err(err) =
+        std::fs::create_dir_all(SETTINGS_DIR).and_then(|_| std::fs::write(&path, contents))
+    {
+        warn!("Could not save settings to {}: {err}", path.display());
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+/// Returns the stored settings of the `group`, if there are any
+#[cfg(target_arch = "wasm32")]
+pub fn load(group: &str) -> Option<String> {
+    local_storage()?
+        .get_item(&format!("{STORAGE_PREFIX}/{group}"))
+        .ok()?
+}
+
+/// Stores the settings of the `group`, failing to do so is not fatal
+#[cfg(target_arch = "wasm32")]
+pub fn save(group: &str, contents: &str) {
+    let stored = local_storage()
+        .map(|storage| storage.set_item(&format!("{STORAGE_PREFIX}/{group}"), contents));
+
+    if !matches!(stored, Some(Ok(_))) {
+        warn!("Could not save settings to local storage: {group}");
+    }
+}
+
+/// Parses `key=value` lines, anything malformed is skipped
+pub fn parse(contents: &str) -> HashMap<&str, &str> {
+    contents
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect()
+}
@@ -1,13 +1,27 @@
+use crate::actions::{Action, Actions, InputMap};
+use crate::audio::{PlaySound, SoundCue};
+use crate::battle::damage::{FlashWithParent, HitFeedback};
+use crate::battle::health_bars::{create_sheep_hp_bar, update_health_bars, StatBars};
+use crate::battle::status::{AreaAttack, Side, StatusEffect, StatusEffects};
 use crate::battle::Level;
 use crate::battle_report::LevelReward;
+use crate::breeding::Breeding;
 use bevy::prelude::*;
+use bevy_simple_stat_bars::prelude::*;
 use iyes_loopless::prelude::*;
 
+use rand::seq::SliceRandom;
 use rand::{thread_rng, Rng};
 
-use crate::ui::{write_text, AsciiSheet};
+use crate::dialog::{no_modal_open, ConfirmDialog, DialogAction, DialogConfirmed};
+use crate::genetics::Genome;
+use crate::naming::random_name;
+use crate::pen::{Overcrowded, PenUpgrades, MAX_PEN_BOUNDS_Y};
+use crate::pointer::Pointer;
+use crate::stats::{BaseStats, Modifier, ModifierSource, Stat, Stats};
+use crate::ui::{write_hud_text, Anchor, AsciiSheet};
 use crate::utils::{bounds_check, Attack, Bounds, Health, Speed, UnloadOnExit};
-use crate::{drag::Drag, GameState, NewGame, ScreenToWorld};
+use crate::{drag::Drag, GameState, NewGame};
 
 pub struct SheepPlugin;
 
@@ -22,10 +36,6 @@ impl Plugin for SheepPlugin {
                 .into(),
         )
         .add_startup_system_to_stage(StartupStage::PreStartup, load_graphics)
-        .add_system_to_stage(
-            CoreStage::PreUpdate,
-            grab_sheep.run_in_state(GameState::Herding),
-        )
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(GameState::Herding)
@@ -37,19 +47,31 @@ impl Plugin for SheepPlugin {
                 .with_system(wobble_sheep)
                 .with_system(shrink_sheep_on_drop)
                 .with_system(update_sheep_ordering)
-                .with_system(keyboard_input)
+                .with_system(regenerate_health)
+                .with_system(show_wounded_health_bars)
+                .with_system(update_health_bars)
+                .with_system(keyboard_input.run_if(no_modal_open))
+                .with_system(start_new_game)
+                .with_system(undo_merge.run_if(no_modal_open))
                 .into(),
         )
+        .add_exit_system(GameState::Herding, forget_last_merge)
         .add_system_to_stage(
             CoreStage::PostUpdate,
             bounds_check.run_in_state(GameState::Herding),
-        );
+        )
+        .add_event::<RankUp>();
+
+        // The sheep can be moved around in the pen and while deploying them for the battle
+        for state in [GameState::Herding, GameState::Deployment] {
+            app.add_system_to_stage(
+                CoreStage::PreUpdate,
+                grab_sheep.run_in_state(state).run_if(no_modal_open),
+            );
+        }
     }
 }
 
-const PEN_BOUNDS_X: Vec2 = Vec2::new(-6.2, 6.2);
-const PEN_BOUNDS_Y: Vec2 = Vec2::new(-6.4, 7.0);
-
 const COUNT_INIT_SHEEP: usize = 10;
 
 const WANDER_TIME_SECS: f32 = 3.0;
@@ -62,12 +84,41 @@ const SHEEP_ROT_WAVELENGTH_SECS_INV: f32 = 8.0;
 const SHEEP_WOBBLE_DRAGGED_SECS_INV: f32 = 24.0;
 
 const SHEEP_DEFAULT_HEALTH: f32 = 20.0;
+/// Part of the max health wounded sheep regenerate every second in the pen
+const SHEEP_REGEN_PER_SEC: f32 = 0.02;
+/// Experience for the first rank, every further rank needs this much more
+const RANK_EXPERIENCE: f32 = 50.0;
+/// Every rank adds this part of the base attack damage and health
+const RANK_STAT_BONUS: f32 = 0.1;
+/// Every spear level adds this much attack range
+const SPEAR_RANGE_BONUS: f32 = 0.2;
+/// Every tank level adds this part of the health
+const TANK_HEALTH_BONUS: f32 = 0.1;
+/// Every few ranks, the player picks a trait for the sheep
+const RANKS_PER_TRAIT_PICK: usize = 3;
+/// Chance of a lamb to inherit one of the traits of its parents
+const LAMB_TRAIT_CHANCE: f64 = 0.5;
+
 const SHEEP_DEFAULT_ATTACK: Attack = Attack {
     attack_damage: 0.2,
     attack_range: 1.0,
     spotting_range: 100.0,
 };
 
+/// Tanks stomp the war machines around them, every tank level adds `TANK_STOMP_DAMAGE`
+const TANK_STOMP_DAMAGE: f32 = 4.0;
+const TANK_STOMP_RADIUS: f32 = 1.5;
+const TANK_STOMP_STUN_SECS: f32 = 0.5;
+const TANK_STOMP_EVERY_SECS: f32 = 4.0;
+/// Medics shield the sheep around them, every medic level adds `MEDIC_SHIELD`
+const MEDIC_SHIELD: f32 = 6.0;
+const MEDIC_SHIELD_RADIUS: f32 = 2.0;
+const MEDIC_SHIELD_SECS: f32 = 3.0;
+const MEDIC_SHIELD_EVERY_SECS: f32 = 5.0;
+/// Spears poison the war machines they hit, every spear level adds `SPEAR_POISON_PER_SEC`
+const SPEAR_POISON_PER_SEC: f32 = 1.5;
+const SPEAR_POISON_SECS: f32 = 2.0;
+
 #[derive(Copy, Clone)]
 pub struct SheepLevels {
     base: usize,
@@ -87,6 +138,16 @@ impl Default for SheepLevels {
     }
 }
 
+impl SheepLevels {
+    fn has(&self, level_trait: Trait) -> bool {
+        match level_trait {
+            Trait::Spear => self.spear > 0,
+            Trait::Tank => self.tank > 0,
+            Trait::Medic => self.medic > 0,
+        }
+    }
+}
+
 impl std::ops::Add<Self> for SheepLevels {
     type Output = Self;
 
@@ -100,36 +161,247 @@ impl std::ops::Add<Self> for SheepLevels {
     }
 }
 
-#[derive(Component, Default)]
+/// The traits a sheep can get from merging or ranking up
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Trait {
+    Spear,
+    Tank,
+    Medic,
+}
+
+impl Trait {
+    pub const ALL: [Trait; 3] = [Trait::Spear, Trait::Tank, Trait::Medic];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Trait::Spear => "spear",
+            Trait::Tank => "tank",
+            Trait::Medic => "medic",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Trait::Spear => "long attack range, poisons",
+            Trait::Tank => "stronger, stuns nearby",
+            Trait::Medic => "shields other sheep",
+        }
+    }
+}
+
+/// Sent when a sheep reaches a new rank in battle
+pub struct RankUp(pub Entity);
+
+#[derive(Component, Default, Clone)]
 pub struct Sheep {
     // In future we can put all the sheep traits here
-    color: f32,
+    name: String,
+    /// The level of the game the sheep was born in
+    birth_level: usize,
+    /// Names of all the sheep that were merged into this one
+    lineage: Vec<String>,
+    genome: Genome,
     levels: SheepLevels,
+    /// Ranks are earned with experience from fighting, unlike levels from merging
+    rank: usize,
+    /// Experience towards the next rank
+    experience: f32,
+    /// Traits the player still has to pick
+    trait_picks: usize,
+    /// Battles the sheep fought in
+    battles: usize,
+    /// War machines the sheep landed the final blow on
+    kills: usize,
 }
 
 impl Sheep {
-    fn from_col(color: f32) -> Self {
-        Self { color, ..default() }
+    fn new(genome: Genome, birth_level: usize) -> Self {
+        Self {
+            name: random_name(),
+            birth_level,
+            genome,
+            ..default()
+        }
     }
 
+    /// The merged sheep keeps the better rank and the experience of both sheep. It goes by the name
+    /// of the stronger one and remembers the other one in its lineage.
     fn combine(&self, other: &Self) -> Self {
-        let mut rng = thread_rng();
-        Self {
-            color: 0.1f32.max((self.color + other.color) / 2.0 + rng.gen_range(-0.1..=0.1)),
+        let (stronger, weaker) = match self.sum_levels() > other.sum_levels() {
+            true => (self, other),
+            false => (other, self),
+        };
+        let mut lineage = stronger.lineage.clone();
+        lineage.extend(weaker.lineage.iter().cloned());
+        lineage.push(weaker.name.clone());
+
+        let mut sheep = Self {
+            name: stronger.name.clone(),
+            birth_level: self.birth_level.min(other.birth_level),
+            lineage,
+            genome: self.genome.mix(&other.genome),
             levels: self.levels + other.levels,
+            rank: self.rank.max(other.rank),
+            experience: 0.0,
+            trait_picks: self.trait_picks + other.trait_picks,
+            battles: self.battles.max(other.battles),
+            kills: self.kills + other.kills,
+        };
+        sheep.gain_experience(self.experience + other.experience);
+        sheep
+    }
+
+    /// The lamb is a level 1 sheep with the colour of its parents, it might inherit one of their
+    /// traits instead of the base level. Its colour makes some traits more likely.
+    pub fn breed(&self, other: &Self, birth_level: usize) -> Self {
+        let mut rng = thread_rng();
+        let mut lamb = Self::new(self.genome.mix(&other.genome), birth_level);
+
+        let traits = Trait::ALL
+            .into_iter()
+            .filter(|inherited| self.levels.has(*inherited) || other.levels.has(*inherited))
+            .collect::<Vec<_>>();
+        let favoured = lamb.genome.favoured_trait();
+        let inherited = match traits.contains(&favoured) {
+            true => Some(&favoured),
+            false => traits.choose(&mut rng),
+        };
+
+        if let Some(inherited) = inherited {
+            if rng.gen_bool(LAMB_TRAIT_CHANCE + lamb.genome.rarity.trait_chance_bonus()) {
+                lamb.levels.base = 0;
+                lamb.add_trait(*inherited);
+            }
+        }
+
+        lamb
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// When the sheep was born and which sheep were merged into it
+    pub fn history(&self) -> String {
+        match self.lineage.len() {
+            0 => format!("Born in lvl {}", self.birth_level),
+            1..=3 => format!(
+                "Born in lvl {}, merged with {}",
+                self.birth_level,
+                self.lineage.join(", ")
+            ),
+            merged => format!(
+                "Born in lvl {}, merged with {merged} sheep",
+                self.birth_level
+            ),
+        }
+    }
+
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    pub fn battles(&self) -> usize {
+        self.battles
+    }
+
+    pub fn kills(&self) -> usize {
+        self.kills
+    }
+
+    pub fn join_battle(&mut self) {
+        self.battles += 1;
+    }
+
+    pub fn add_kill(&mut self) {
+        self.kills += 1;
+    }
+
+    /// Short list of the levels, like `B1 S2 T1`
+    pub fn levels_label(&self) -> String {
+        [
+            ("B", self.levels.base),
+            ("S", self.levels.spear),
+            ("T", self.levels.tank),
+            ("M", self.levels.medic),
+        ]
+        .iter()
+        .filter(|(_, level)| *level > 0)
+        .map(|(initial, level)| format!("{initial}{level}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    pub fn has_trait(&self, level_trait: Trait) -> bool {
+        self.levels.has(level_trait)
+    }
+
+    pub fn experience(&self) -> f32 {
+        self.experience
+    }
+
+    /// Experience needed to reach the next rank
+    pub fn next_rank_experience(&self) -> f32 {
+        RANK_EXPERIENCE * (self.rank + 1) as f32
+    }
+
+    /// Adds the `experience` and returns whether the sheep reached a new rank
+    pub fn gain_experience(&mut self, experience: f32) -> bool {
+        let rank = self.rank;
+        self.experience += experience;
+
+        while self.experience >= self.next_rank_experience() {
+            self.experience -= self.next_rank_experience();
+            self.rank += 1;
+            if self.rank % RANKS_PER_TRAIT_PICK == 0 {
+                self.trait_picks += 1;
+            }
+        }
+
+        self.rank > rank
+    }
+
+    pub fn trait_picks(&self) -> usize {
+        self.trait_picks
+    }
+
+    /// Uses one of the trait picks to level up the `picked` trait
+    pub fn pick_trait(&mut self, picked: Trait) {
+        if self.trait_picks == 0 {
+            return;
+        }
+        self.trait_picks -= 1;
+        self.add_trait(picked);
+    }
+
+    /// Levels up the `added` trait
+    pub fn add_trait(&mut self, added: Trait) {
+        match added {
+            Trait::Spear => self.levels.spear += 1,
+            Trait::Tank => self.levels.tank += 1,
+            Trait::Medic => self.levels.medic += 1,
         }
     }
 
+    fn rank_bonus(&self) -> f32 {
+        1.0 + RANK_STAT_BONUS * self.rank as f32
+    }
+
     /// TODO: Currently we sum all the levels times the SHEEP_DEFAULT_ATTACK to get the
     /// `attack_damage`. Should probably be modified based on the different trait + combine RNG.
     pub fn sum_levels(&self) -> f32 {
         (self.levels.base + self.levels.spear + self.levels.tank + self.levels.medic) as f32
     }
 
-    /// If this component is attached to the `sheep` entity, it will attack the nearest war
-    /// machine.
-    pub fn attack_component(&self) -> Attack {
-        Attack {
+    /// The stats of its levels, with diminishing speed
+    fn base_stats(&self) -> BaseStats {
+        BaseStats {
+            speed: SHEEP_WANDER_SPEED * (self.sum_levels()).log2() + 1.0,
+            health: SHEEP_DEFAULT_HEALTH * (self.sum_levels()),
             attack_damage: SHEEP_DEFAULT_ATTACK.attack_damage * (self.sum_levels() + 1.0) / 2.0,
             attack_range: SHEEP_DEFAULT_ATTACK.attack_range
                 * ((self.sum_levels() / 2.0).log2() + 0.2).clamp(1.0, 2.0),
@@ -138,14 +410,107 @@ impl Sheep {
         }
     }
 
-    /// Diminishing speed
-    pub fn speed_component(&self) -> Speed {
-        Speed(SHEEP_WANDER_SPEED * (self.sum_levels()).log2() + 1.0)
+    /// Sets the base stats and the modifiers of its traits, rank and genes. The modifiers of
+    /// other sources, like status effects, stay.
+    pub fn update_stats(&self, stats: &mut Stats) {
+        stats.set_base(self.base_stats());
+
+        stats.set(
+            ModifierSource::Trait,
+            Stat::AttackRange,
+            Modifier::add(SPEAR_RANGE_BONUS * self.levels.spear as f32),
+        );
+        stats.set(
+            ModifierSource::Trait,
+            Stat::Health,
+            Modifier::multiply(1.0 + TANK_HEALTH_BONUS * self.levels.tank as f32),
+        );
+
+        for stat in [Stat::AttackDamage, Stat::Health] {
+            stats.set(
+                ModifierSource::Rank,
+                stat,
+                Modifier::multiply(self.rank_bonus()),
+            );
+        }
+
+        let rarity = Modifier::multiply(self.genome.rarity.stat_bonus());
+        stats.set(ModifierSource::Genes, Stat::AttackDamage, rarity);
+        stats.set(ModifierSource::Genes, Stat::Health, rarity);
+        stats.set(
+            ModifierSource::Genes,
+            Stat::Speed,
+            Modifier::multiply(self.genome.pattern.speed_bonus()),
+        );
+        stats.set(
+            ModifierSource::Genes,
+            Stat::AttackRange,
+            Modifier::multiply(self.genome.pattern.attack_range_bonus()),
+        );
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::new(self.base_stats());
+        self.update_stats(&mut stats);
+        stats
+    }
+
+    /// The area attacks of its traits, with the seconds between them
+    pub fn area_attacks(&self) -> Vec<(AreaAttack, f32)> {
+        let mut attacks = Vec::new();
+        if self.levels.tank > 0 {
+            attacks.push((
+                AreaAttack {
+                    radius: TANK_STOMP_RADIUS,
+                    damage: TANK_STOMP_DAMAGE * self.levels.tank as f32,
+                    effect: Some(StatusEffect::Stun {
+                        secs: TANK_STOMP_STUN_SECS,
+                    }),
+                    targets: Side::WarMachines,
+                },
+                TANK_STOMP_EVERY_SECS,
+            ));
+        }
+        if self.levels.medic > 0 {
+            attacks.push((
+                AreaAttack {
+                    radius: MEDIC_SHIELD_RADIUS,
+                    damage: 0.0,
+                    effect: Some(StatusEffect::Shield {
+                        amount: MEDIC_SHIELD * self.levels.medic as f32,
+                        secs: MEDIC_SHIELD_SECS,
+                    }),
+                    targets: Side::Sheep,
+                },
+                MEDIC_SHIELD_EVERY_SECS,
+            ));
+        }
+        attacks
+    }
+
+    /// The status effect put on the war machines it hits
+    pub fn on_hit(&self) -> Option<StatusEffect> {
+        (self.levels.spear > 0).then(|| StatusEffect::Poison {
+            per_sec: SPEAR_POISON_PER_SEC * self.levels.spear as f32,
+            secs: SPEAR_POISON_SECS,
+        })
     }
+}
 
-    pub fn health_component(&self) -> Health {
-        let hp = SHEEP_DEFAULT_HEALTH * (self.sum_levels());
-        Health::new(hp)
+/// The sheep that ranked up in battle get stronger right away
+pub fn rank_up_sheep(
+    mut rank_ups: EventReader<RankUp>,
+    mut sheep_q: Query<(&Sheep, &Transform, &mut Stats)>,
+    mut play_sound: EventWriter<PlaySound>,
+) {
+    for RankUp(entity) in rank_ups.iter() {
+        if let Ok((sheep, transform, mut stats)) = sheep_q.get_mut(*entity) {
+            sheep.update_stats(&mut stats);
+            play_sound.send(PlaySound::at(
+                SoundCue::SheepRankUp,
+                transform.translation.truncate(),
+            ));
+        }
     }
 }
 
@@ -191,18 +556,19 @@ impl Wander {
     }
 }
 
-fn spawn_sheep(
+/// Spawns a sheep kept inside of the `bounds`, usually the ones of the current pen
+pub fn spawn_sheep(
     commands: &mut Commands,
     texture: &SheepSprites,
     transform: Transform,
+    bounds: Bounds,
     sheep: Sheep,
 ) -> Entity {
     let mut transform = transform;
     transform.rotation = Quat::IDENTITY;
 
-    let attack = sheep.attack_component();
-    let speed = sheep.speed_component();
-    let health = sheep.health_component();
+    let stats = sheep.stats();
+    let name = Name::from(sheep.name());
 
     let sheep = commands
         .spawn_bundle(SpriteSheetBundle {
@@ -211,7 +577,7 @@ fn spawn_sheep(
             sprite: TextureAtlasSprite {
                 index: 1,
                 custom_size: Some(Vec2::new(20.0, 19.0) / 16.0),
-                color: Color::WHITE * sheep.color,
+                color: sheep.genome.color(),
                 ..default()
             },
             ..default()
@@ -226,13 +592,14 @@ fn spawn_sheep(
                 false => WanderState::Idling,
             },
         ))
-        .insert(Bounds {
-            x: (PEN_BOUNDS_X.x, PEN_BOUNDS_X.y),
-            y: (PEN_BOUNDS_Y.x, PEN_BOUNDS_Y.y),
-        })
-        .insert(speed)
-        .insert(health)
-        .insert(attack)
+        .insert(bounds)
+        .insert(stats.speed())
+        .insert(stats.health())
+        .insert(stats.attack())
+        .insert(stats)
+        .insert(HitFeedback::default())
+        .insert(StatusEffects::default())
+        .insert(name)
         .id();
 
     let head = commands
@@ -249,6 +616,7 @@ fn spawn_sheep(
             },
             ..default()
         })
+        .insert(FlashWithParent)
         .id();
 
     commands.entity(sheep).add_child(head);
@@ -256,6 +624,56 @@ fn spawn_sheep(
     sheep
 }
 
+fn regenerate_health(time: Res<Time>, mut sheep_q: Query<&mut Health, With<Sheep>>) {
+    for mut health in sheep_q.iter_mut() {
+        if health.current < health.max {
+            health.current = (health.current
+                + health.max * SHEEP_REGEN_PER_SEC * time.delta_seconds())
+            .min(health.max);
+        }
+    }
+}
+
+/// Wounded sheep show their health bars in the pen, until they are healed
+fn show_wounded_health_bars(
+    mut commands: Commands,
+    sheep_q: Query<(Entity, &Health, Option<&StatBars>), With<Sheep>>,
+    bars_q: Query<(), With<StatBarValue>>,
+) {
+    for (sheep, health, stat_bars) in sheep_q.iter() {
+        // The bars from the battle are gone by now
+        let bar = stat_bars
+            .map(|stat_bars| stat_bars.hp)
+            .filter(|bar| bars_q.get(*bar).is_ok());
+
+        match (health.ratio() < 1.0, bar) {
+            (true, None) => create_sheep_hp_bar(sheep, &mut commands),
+            (false, Some(bar)) => {
+                commands.entity(bar).despawn_recursive();
+                commands.entity(sheep).remove::<StatBars>();
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Spawns a sheep with only `health_ratio` of its health left
+pub fn spawn_wounded_sheep(
+    commands: &mut Commands,
+    texture: &SheepSprites,
+    transform: Transform,
+    bounds: Bounds,
+    sheep: Sheep,
+    health_ratio: f32,
+) -> Entity {
+    let mut health = sheep.stats().health();
+    health.current = health.max * health_ratio.clamp(0.0, 1.0);
+
+    let sheep = spawn_sheep(commands, texture, transform, bounds, sheep);
+    commands.entity(sheep).insert(health);
+    sheep
+}
+
 #[derive(Component)]
 pub struct SheepParent;
 
@@ -267,7 +685,9 @@ fn init_new_game(
     // Remove old sheep parents
     sheep_parent_q.for_each(|sheep_parent| commands.entity(sheep_parent).despawn_recursive());
 
-    let sheep = spawn_n_sheep(&mut commands, texture, COUNT_INIT_SHEEP);
+    // The upgrades of the last game are reset as well
+    let pen = PenUpgrades::default().pen_bounds();
+    let sheep = spawn_n_sheep(&mut commands, texture, &pen, 1, COUNT_INIT_SHEEP);
 
     commands
         .spawn_bundle(SpatialBundle::default())
@@ -282,29 +702,46 @@ fn init_new_game(
 fn add_level_reward_sheep(
     mut commands: Commands,
     texture: Res<SheepSprites>,
+    level: Res<Level>,
     level_reward: Res<LevelReward>,
+    upgrades: Res<PenUpgrades>,
     sheep_parent: Query<Entity, With<SheepParent>>,
 ) {
-    let sheep = spawn_n_sheep(&mut commands, texture, level_reward.0);
+    let sheep = spawn_n_sheep(
+        &mut commands,
+        texture,
+        &upgrades.pen_bounds(),
+        level.0,
+        level_reward.sheep,
+    );
 
     commands.entity(sheep_parent.single()).push_children(&sheep);
     commands.remove_resource::<LevelReward>();
 }
 
-fn setup_ui(mut commands: Commands, ascii_sheet: Res<AsciiSheet>, level: Res<Level>) {
-    let start_battle_text = write_text(
+fn setup_ui(
+    mut commands: Commands,
+    ascii_sheet: Res<AsciiSheet>,
+    level: Res<Level>,
+    input_map: Res<InputMap>,
+) {
+    let start_battle_text = write_hud_text(
         &mut commands,
         &ascii_sheet,
-        Vec2::new(-2.6, -4.3).extend(50.0),
+        Anchor::Bottom,
+        Vec2::new(0.0, 0.5),
+        50.0,
         Color::WHITE,
-        "Press SPACE to fight!",
+        format!("Press {} to fight!", input_map.label(Action::StartBattle)).as_str(),
     );
 
     let lvl_string = level.0;
-    let level_text = write_text(
+    let level_text = write_hud_text(
         &mut commands,
         &ascii_sheet,
-        Vec2::new(6.5, 4.3).extend(50.0),
+        Anchor::TopRight,
+        Vec2::new(-0.5, -0.5),
+        50.0,
         Color::WHITE,
         format!("Lvl: {lvl_string}").as_str(),
     );
@@ -313,40 +750,32 @@ fn setup_ui(mut commands: Commands, ascii_sheet: Res<AsciiSheet>, level: Res<Lev
     commands.entity(level_text).insert(UnloadOnExit);
 }
 
-fn spawn_n_sheep(
+/// Spawns `num_sheep` new sheep somewhere in the `pen`, born in the level `birth_level`
+pub fn spawn_n_sheep(
     commands: &mut Commands,
     texture: Res<SheepSprites>,
+    pen: &Bounds,
+    birth_level: usize,
     num_sheep: usize,
 ) -> Vec<Entity> {
     let mut rng = thread_rng();
 
     let mut sheep = Vec::with_capacity(num_sheep);
-    for i in 0..num_sheep {
-        let new_sheep = spawn_sheep(
+    for _ in 0..num_sheep {
+        sheep.push(spawn_sheep(
             commands,
             &texture,
             Transform {
                 translation: Vec3::new(
-                    rng.gen_range(PEN_BOUNDS_X.x..=PEN_BOUNDS_X.y),
-                    rng.gen_range(PEN_BOUNDS_Y.x..=PEN_BOUNDS_Y.y),
+                    rng.gen_range(pen.x.0..=pen.x.1),
+                    rng.gen_range(pen.y.0..=pen.y.1),
                     10.0,
                 ),
                 ..default()
             },
-            Sheep::from_col(if rng.gen_range(0.0..=1.0) >= 0.2 {
-                // White sheep more likely than black sheep
-                rng.gen_range(0.8..=1.0)
-            } else {
-                rng.gen_range(0.1..=0.3)
-            }),
-        );
-
-        sheep.push(
-            commands
-                .entity(new_sheep)
-                .insert(Name::from(format!("Sheep_{i}")))
-                .id(),
-        );
+            pen.clone(),
+            Sheep::new(Genome::random(), birth_level),
+        ));
     }
 
     sheep
@@ -355,17 +784,11 @@ fn spawn_n_sheep(
 fn grab_sheep(
     mut commands: Commands,
     sheep_q: Query<(Entity, &Transform), With<Sheep>>,
-    mouse_btn: Res<Input<MouseButton>>,
-    windows: Res<Windows>,
-    camera: Query<(&Camera, &GlobalTransform)>,
+    pointer: Res<Pointer>,
+    mut play_sound: EventWriter<PlaySound>,
 ) {
-    let window = windows.get_primary().unwrap();
-
-    if mouse_btn.just_pressed(MouseButton::Left) {
-        if let Some(mouse_pos) = window.cursor_position() {
-            // Convert screen coordinates to world coordinates
-            let mouse_pos = mouse_pos.screen_to_world(windows, camera);
-
+    if pointer.just_pressed() {
+        if let Some(mouse_pos) = pointer.position {
             // Detect sheep
             let mut sheep = sheep_q
                 .iter()
@@ -381,11 +804,15 @@ fn grab_sheep(
                     .unwrap()
             });
 
-            if let Some((sheep, _)) = sheep.get(0) {
+            if let Some((sheep, transform)) = sheep.get(0) {
                 commands.entity(*sheep).insert(Drag);
+                play_sound.send(PlaySound::at(
+                    SoundCue::SheepPickup,
+                    transform.translation.truncate(),
+                ));
             }
         }
-    } else if mouse_btn.just_released(MouseButton::Left) {
+    } else if pointer.just_released() {
         for (sheep, _) in &sheep_q {
             commands.entity(sheep).remove::<Drag>();
         }
@@ -395,41 +822,130 @@ fn grab_sheep(
 fn drop_sheep(
     mut commands: Commands,
     texture: Res<SheepSprites>,
+    upgrades: Res<PenUpgrades>,
     dropped: RemovedComponents<Drag>,
-    sheep: Query<(Entity, &Sheep, &Transform)>,
+    sheep: Query<(Entity, &Sheep, &Transform, &Health)>,
     sheep_parent: Query<Entity, With<SheepParent>>,
+    mut play_sound: EventWriter<PlaySound>,
 ) {
     for drop in dropped.iter() {
-        if let Ok((_, sheep_component, dropped_transform)) = sheep.get(drop) {
-            if let Some((collided, collided_sheep_component, collided_transform)) = sheep
-                .iter()
-                .filter(|(_, _, transform)| {
-                    transform
-                        .translation
-                        .truncate()
-                        .distance(dropped_transform.translation.truncate())
-                        <= transform.scale.x
-                })
-                .find(|(entity, _, _)| entity.id() != drop.id())
+        if let Ok((_, sheep_component, dropped_transform, dropped_health)) = sheep.get(drop) {
+            if let Some((collided, collided_sheep_component, collided_transform, collided_health)) =
+                sheep
+                    .iter()
+                    .filter(|(_, _, transform, _)| {
+                        transform
+                            .translation
+                            .truncate()
+                            .distance(dropped_transform.translation.truncate())
+                            <= transform.scale.x
+                    })
+                    .find(|(entity, _, _, _)| entity.id() != drop.id())
             {
                 commands.entity(drop).despawn_recursive();
                 commands.entity(collided).despawn_recursive();
 
-                let new_sheep = spawn_sheep(
+                // The merged sheep is as wounded as the two sheep together
+                let health_ratio = (dropped_health.current + collided_health.current)
+                    / (dropped_health.max + collided_health.max);
+                let new_sheep = spawn_wounded_sheep(
                     &mut commands,
                     &texture,
                     *collided_transform,
+                    upgrades.pen_bounds(),
                     sheep_component.combine(collided_sheep_component),
+                    health_ratio,
                 );
 
                 commands.entity(sheep_parent.single()).add_child(new_sheep);
+                commands.insert_resource(LastMerge {
+                    merged: new_sheep,
+                    parents: [
+                        (
+                            sheep_component.clone(),
+                            Transform::from_translation(dropped_transform.translation),
+                            dropped_health.ratio(),
+                        ),
+                        (
+                            collided_sheep_component.clone(),
+                            Transform::from_translation(collided_transform.translation),
+                            collided_health.ratio(),
+                        ),
+                    ],
+                });
+                play_sound.send(PlaySound::at(
+                    SoundCue::SheepMerge,
+                    collided_transform.translation.truncate(),
+                ));
+            } else {
+                play_sound.send(PlaySound::at(
+                    SoundCue::SheepDrop,
+                    dropped_transform.translation.truncate(),
+                ));
             }
         }
     }
 }
 
+/// The latest merge in the pen, so it can be undone until the battle starts
+struct LastMerge {
+    merged: Entity,
+    /// The sheep, where they were and how much of their health was left
+    parents: [(Sheep, Transform, f32); 2],
+}
+
+/// Splits the sheep of the latest merge back into the sheep it was merged from
+fn undo_merge(
+    mut commands: Commands,
+    actions: Actions,
+    texture: Res<SheepSprites>,
+    upgrades: Res<PenUpgrades>,
+    last_merge: Option<Res<LastMerge>>,
+    sheep_q: Query<&Transform, With<Sheep>>,
+    sheep_parent: Query<Entity, With<SheepParent>>,
+    mut play_sound: EventWriter<PlaySound>,
+) {
+    if !actions.just_released(Action::Undo) {
+        return;
+    }
+
+    let last_merge = match last_merge {
+        Some(last_merge) => last_merge,
+        None => return,
+    };
+
+    // The merged sheep may have been sold or merged again since
+    if let Ok(merged_transform) = sheep_q.get(last_merge.merged) {
+        commands.entity(last_merge.merged).despawn_recursive();
+
+        for (sheep, transform, health_ratio) in last_merge.parents.iter() {
+            let parent = spawn_wounded_sheep(
+                &mut commands,
+                &texture,
+                *transform,
+                upgrades.pen_bounds(),
+                sheep.clone(),
+                *health_ratio,
+            );
+            commands.entity(sheep_parent.single()).add_child(parent);
+        }
+
+        play_sound.send(PlaySound::at(
+            SoundCue::SheepDrop,
+            merged_transform.translation.truncate(),
+        ));
+    }
+
+    commands.remove_resource::<LastMerge>();
+}
+
+fn forget_last_merge(mut commands: Commands) {
+    commands.remove_resource::<LastMerge>();
+}
+
+/// The select box, a child of the selected sheep
 #[derive(Component)]
-struct Select;
+pub struct Select;
 
 #[derive(Component)]
 struct SelectedText;
@@ -485,12 +1001,22 @@ fn sheep_select(
 
         // Add new text
         let lvl_string = sheep.sum_levels();
-        let sheep_stats = write_text(
+        let sheep_stats = write_hud_text(
             &mut commands,
             &ascii_sheet,
-            Vec2::new(-1.0, 4.3).extend(50.0),
+            Anchor::Top,
+            Vec2::new(0.0, -0.5),
+            50.0,
             Color::WHITE,
-            format!("Sheep lvl: {lvl_string}").as_str(),
+            format!(
+                "Sheep lvl: {lvl_string}  rank: {} ({:.0}/{:.0} xp)\n{}\n{}",
+                sheep.rank(),
+                sheep.experience(),
+                sheep.next_rank_experience(),
+                sheep.genome.description(),
+                sheep.history()
+            )
+            .as_str(),
         );
         commands
             .entity(sheep_stats)
@@ -529,7 +1055,10 @@ fn update_select_box(mut q: Query<&mut Visibility, With<Select>>, dragged: Query
 }
 
 pub fn wander(
-    mut sheeps: Query<(Entity, &mut Wander, &mut Transform, &Speed), (With<Sheep>, Without<Drag>)>,
+    mut sheeps: Query<
+        (Entity, &mut Wander, &mut Transform, &Speed),
+        (With<Sheep>, Without<Drag>, Without<Breeding>),
+    >,
     time: Res<Time>,
 ) {
     for (entity, mut sheep, mut transform, speed) in sheeps.iter_mut() {
@@ -598,15 +1127,15 @@ pub fn update_sheep_ordering(
         transform.translation.z = match dragged.get(entity) {
             Ok(_) => 9.9,
             Err(_) => {
-                9.9 - ((transform.translation.y - PEN_BOUNDS_Y.x).abs()
-                    / (PEN_BOUNDS_Y.y - PEN_BOUNDS_Y.x).abs())
+                9.9 - ((transform.translation.y - MAX_PEN_BOUNDS_Y.x).abs()
+                    / (MAX_PEN_BOUNDS_Y.y - MAX_PEN_BOUNDS_Y.x).abs())
                     * 9.79
             }
         }
     }
 }
 
-struct SheepSprites(Handle<TextureAtlas>);
+pub struct SheepSprites(Handle<TextureAtlas>);
 
 fn load_graphics(
     mut commands: Commands,
@@ -626,17 +1155,29 @@ fn load_graphics(
     commands.insert_resource(SheepSprites(atlas_handle));
 }
 
-fn keyboard_input(
+fn keyboard_input(mut commands: Commands, actions: Actions, overcrowded: Option<Res<Overcrowded>>) {
+    // The sheep that don't fit into the pen have to be sold or merged first
+    if actions.just_released(Action::StartBattle) && overcrowded.is_none() {
+        commands.insert_resource(NextState(GameState::Deployment));
+    }
+
+    if actions.just_released(Action::NewGame) {
+        commands.insert_resource(ConfirmDialog::new(
+            "Start a new game?\n\nAll of your sheep will be lost!",
+            DialogAction::NewGame,
+        ));
+    }
+}
+
+fn start_new_game(
     mut commands: Commands,
-    keys: ResMut<Input<KeyCode>>,
+    mut confirmed: EventReader<DialogConfirmed>,
     sheep_q: Query<Entity, With<SheepParent>>,
 ) {
-    if keys.just_released(KeyCode::Space) {
-        commands.insert_resource(NextState(GameState::Battle));
-    }
-
-    // On `N` start a new game
-    if keys.just_released(KeyCode::N) {
+    if confirmed
+        .iter()
+        .any(|DialogConfirmed(action)| *action == DialogAction::NewGame)
+    {
         sheep_q.for_each(|ent| commands.entity(ent).despawn_recursive());
         commands.insert_resource(NewGame);
         commands.insert_resource(NextState(GameState::Herding));
@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+
+use crate::utils::{Attack, Health, Speed};
+
+/// The stats that modifiers change
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Stat {
+    Speed,
+    Health,
+    AttackDamage,
+    AttackRange,
+    SpottingRange,
+}
+
+/// Where a modifier comes from, every source has at most one modifier per stat
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ModifierSource {
+    /// The traits of a sheep
+    Trait,
+    /// The rank of a sheep
+    Rank,
+    /// The colour genes of a sheep
+    Genes,
+    /// Status effects, like being slowed or stunned
+    Status,
+    /// Upgrades bought in the shop, like the hay
+    Upgrade,
+    /// War machines get stronger with every level of the game
+    Difficulty,
+}
+
+/// Adds `add` to the base value and multiplies the sum by `multiply`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Modifier {
+    pub add: f32,
+    pub multiply: f32,
+}
+
+impl Modifier {
+    pub fn add(value: f32) -> Self {
+        Self {
+            add: value,
+            multiply: 1.0,
+        }
+    }
+
+    pub fn multiply(factor: f32) -> Self {
+        Self {
+            add: 0.0,
+            multiply: factor,
+        }
+    }
+}
+
+/// The stats before any modifiers
+#[derive(Copy, Clone)]
+pub struct BaseStats {
+    pub speed: f32,
+    pub health: f32,
+    pub attack_damage: f32,
+    pub attack_range: f32,
+    pub spotting_range: f32,
+}
+
+impl BaseStats {
+    fn get(&self, stat: Stat) -> f32 {
+        match stat {
+            Stat::Speed => self.speed,
+            Stat::Health => self.health,
+            Stat::AttackDamage => self.attack_damage,
+            Stat::AttackRange => self.attack_range,
+            Stat::SpottingRange => self.spotting_range,
+        }
+    }
+}
+
+/// Base stats with a stack of modifiers on them. `Speed`, `Health` and `Attack` hold the
+/// effective values, `apply_stats` recomputes them whenever this component changes.
+#[derive(Component, Clone)]
+pub struct Stats {
+    base: BaseStats,
+    modifiers: Vec<(ModifierSource, Stat, Modifier)>,
+}
+
+impl Stats {
+    pub fn new(base: BaseStats) -> Self {
+        Self {
+            base,
+            modifiers: Vec::new(),
+        }
+    }
+
+    pub fn set_base(&mut self, base: BaseStats) {
+        self.base = base;
+    }
+
+    pub fn modifier(&self, source: ModifierSource, stat: Stat) -> Option<Modifier> {
+        self.modifiers
+            .iter()
+            .find(|(modifier_source, modifier_stat, _)| {
+                *modifier_source == source && *modifier_stat == stat
+            })
+            .map(|(_, _, modifier)| *modifier)
+    }
+
+    /// Replaces the modifier of the `source` on the `stat`
+    pub fn set(&mut self, source: ModifierSource, stat: Stat, modifier: Modifier) {
+        self.remove(source, stat);
+        self.modifiers.push((source, stat, modifier));
+    }
+
+    pub fn remove(&mut self, source: ModifierSource, stat: Stat) {
+        self.modifiers
+            .retain(|(modifier_source, modifier_stat, _)| {
+                *modifier_source != source || *modifier_stat != stat
+            });
+    }
+
+    /// Removes every modifier of the `source`
+    pub fn clear(&mut self, source: ModifierSource) {
+        self.modifiers
+            .retain(|(modifier_source, _, _)| *modifier_source != source);
+    }
+
+    /// All additions are summed up first, then the sum is multiplied by all the factors
+    pub fn value(&self, stat: Stat) -> f32 {
+        let (add, multiply) = self
+            .modifiers
+            .iter()
+            .filter(|(_, modifier_stat, _)| *modifier_stat == stat)
+            .fold((0.0, 1.0), |(add, multiply), (_, _, modifier)| {
+                (add + modifier.add, multiply * modifier.multiply)
+            });
+
+        (self.base.get(stat) + add) * multiply
+    }
+
+    pub fn speed(&self) -> Speed {
+        Speed(self.value(Stat::Speed))
+    }
+
+    /// Full health
+    pub fn health(&self) -> Health {
+        Health::new(self.value(Stat::Health))
+    }
+
+    pub fn attack(&self) -> Attack {
+        Attack {
+            attack_damage: self.value(Stat::AttackDamage),
+            attack_range: self.value(Stat::AttackRange),
+            spotting_range: self.value(Stat::SpottingRange),
+        }
+    }
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PostUpdate, apply_stats);
+    }
+}
+
+/// Recomputes the effective stats, wounded entities keep their share of health
+fn apply_stats(mut q: Query<(&Stats, &mut Speed, &mut Health, &mut Attack), Changed<Stats>>) {
+    for (stats, mut speed, mut health, mut attack) in q.iter_mut() {
+        let health_ratio = health.ratio();
+
+        *speed = stats.speed();
+        *attack = stats.attack();
+        health.max = stats.value(Stat::Health);
+        health.current = health.max * health_ratio;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> Stats {
+        Stats::new(BaseStats {
+            speed: 2.0,
+            health: 100.0,
+            attack_damage: 10.0,
+            attack_range: 1.0,
+            spotting_range: 5.0,
+        })
+    }
+
+    #[test]
+    fn value_adds_before_multiplying() {
+        let mut stats = stats();
+        stats.set(ModifierSource::Trait, Stat::Speed, Modifier::add(1.0));
+        stats.set(ModifierSource::Rank, Stat::Speed, Modifier::multiply(2.0));
+        stats.set(ModifierSource::Genes, Stat::Speed, Modifier::add(3.0));
+        stats.set(ModifierSource::Status, Stat::Speed, Modifier::multiply(0.5));
+
+        assert_eq!(stats.value(Stat::Speed), (2.0 + 1.0 + 3.0) * 2.0 * 0.5);
+        // The other stats keep their base values
+        assert_eq!(stats.value(Stat::Health), 100.0);
+    }
+
+    #[test]
+    fn value_of_stunned_stat_is_zero() {
+        let mut stats = stats();
+        stats.set(
+            ModifierSource::Trait,
+            Stat::AttackDamage,
+            Modifier::add(5.0),
+        );
+        stats.set(
+            ModifierSource::Status,
+            Stat::AttackDamage,
+            Modifier::multiply(0.0),
+        );
+
+        assert_eq!(stats.value(Stat::AttackDamage), 0.0);
+    }
+
+    #[test]
+    fn set_replaces_the_modifier_of_the_source() {
+        let mut stats = stats();
+        stats.set(ModifierSource::Trait, Stat::Health, Modifier::add(10.0));
+        stats.set(ModifierSource::Trait, Stat::Health, Modifier::multiply(1.5));
+
+        assert_eq!(
+            stats.modifier(ModifierSource::Trait, Stat::Health),
+            Some(Modifier::multiply(1.5))
+        );
+        assert_eq!(stats.value(Stat::Health), 150.0);
+    }
+
+    #[test]
+    fn remove_only_takes_the_modifier_of_the_source_on_the_stat() {
+        let mut stats = stats();
+        stats.set(ModifierSource::Trait, Stat::Health, Modifier::add(10.0));
+        stats.set(ModifierSource::Trait, Stat::AttackRange, Modifier::add(0.5));
+        stats.set(ModifierSource::Rank, Stat::Health, Modifier::multiply(2.0));
+
+        stats.remove(ModifierSource::Trait, Stat::Health);
+
+        assert_eq!(stats.modifier(ModifierSource::Trait, Stat::Health), None);
+        assert_eq!(stats.value(Stat::Health), 200.0);
+        assert_eq!(stats.value(Stat::AttackRange), 1.5);
+    }
+
+    #[test]
+    fn clear_removes_every_modifier_of_the_source() {
+        let mut stats = stats();
+        stats.set(ModifierSource::Status, Stat::Speed, Modifier::multiply(0.5));
+        stats.set(
+            ModifierSource::Status,
+            Stat::AttackDamage,
+            Modifier::multiply(0.5),
+        );
+        stats.set(
+            ModifierSource::Difficulty,
+            Stat::AttackDamage,
+            Modifier::multiply(1.5),
+        );
+
+        stats.clear(ModifierSource::Status);
+
+        assert_eq!(stats.value(Stat::Speed), 2.0);
+        assert_eq!(stats.value(Stat::AttackDamage), 15.0);
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::actions::{Action, Actions, InputMap};
+use crate::dialog::{no_modal_open, only_modal_open, ModalApp};
+use crate::sheep::{Sheep, Trait};
+use crate::stats::Stats;
+use crate::ui::{text_size, write_hud_text, Anchor, AsciiSheet, HudAnchor};
+use crate::GameState;
+
+/// If this resource is present, the player picks a trait for a sheep that ranked up. Everything
+/// else in the pen ignores the input until it is picked.
+pub struct TraitPick {
+    sheep: Entity,
+    selected: usize,
+}
+
+/// Marker component for everything that is part of the trait pick
+#[derive(Component)]
+struct TraitPickEntity;
+
+pub struct TraitPickPlugin;
+
+impl Plugin for TraitPickPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_modal::<TraitPick>()
+            .add_system(despawn_trait_pick.run_unless_resource_exists::<TraitPick>())
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::Herding)
+                    .with_system(offer_trait_pick.run_if(no_modal_open))
+                    .with_system(trait_pick_input.run_if(only_modal_open::<TraitPick>))
+                    .into(),
+            )
+            .add_system(
+                render_trait_pick
+                    .run_in_state(GameState::Herding)
+                    .run_if_resource_exists::<TraitPick>(),
+            )
+            .add_exit_system(GameState::Herding, close_trait_pick);
+    }
+}
+
+/// Asks for a trait as soon as a sheep has one to pick
+fn offer_trait_pick(mut commands: Commands, sheep_q: Query<(Entity, &Sheep)>) {
+    if let Some((sheep, _)) = sheep_q.iter().find(|(_, sheep)| sheep.trait_picks() > 0) {
+        commands.insert_resource(TraitPick { sheep, selected: 0 });
+    }
+}
+
+fn trait_pick_input(
+    mut commands: Commands,
+    actions: Actions,
+    mut trait_pick: ResMut<TraitPick>,
+    mut sheep_q: Query<(&mut Sheep, &mut Stats)>,
+) {
+    let (mut sheep, mut stats) = match sheep_q.get_mut(trait_pick.sheep) {
+        Ok(sheep) => sheep,
+        Err(_) => {
+            commands.remove_resource::<TraitPick>();
+            return;
+        }
+    };
+
+    let count = Trait::ALL.len();
+    if actions.just_released(Action::MenuLeft) {
+        trait_pick.selected = (trait_pick.selected + count - 1) % count;
+    } else if actions.just_released(Action::MenuRight) {
+        trait_pick.selected = (trait_pick.selected + 1) % count;
+    } else if actions.just_released(Action::Confirm) {
+        sheep.pick_trait(Trait::ALL[trait_pick.selected]);
+        sheep.update_stats(&mut stats);
+        commands.remove_resource::<TraitPick>();
+    }
+}
+
+fn render_trait_pick(
+    mut commands: Commands,
+    trait_pick: Res<TraitPick>,
+    input_map: Res<InputMap>,
+    ascii_sheet: Res<AsciiSheet>,
+    sheep_q: Query<&Sheep>,
+    trait_pick_q: Query<Entity, With<TraitPickEntity>>,
+) {
+    if !trait_pick.is_changed() {
+        return;
+    }
+
+    trait_pick_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let rank = sheep_q
+        .get(trait_pick.sheep)
+        .map(|sheep| sheep.rank())
+        .unwrap_or_default();
+    let picked = Trait::ALL[trait_pick.selected];
+    let text = format!(
+        "A sheep reached rank {rank}!\n\nPick a trait:  < {} >\n{}\n\n\n{}/{} change   {} pick",
+        picked.label(),
+        picked.description(),
+        input_map.label(Action::MenuLeft),
+        input_map.label(Action::MenuRight),
+        input_map.label(Action::Confirm)
+    );
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.0, 0.0, 0.0, 0.9),
+                custom_size: Some(text_size(&text) + Vec2::splat(1.5)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudAnchor::new(Anchor::Center, Vec2::ZERO, 150.0))
+        .insert(TraitPickEntity)
+        .insert(Name::from("TraitPickBackground"));
+
+    let text = write_hud_text(
+        &mut commands,
+        &ascii_sheet,
+        Anchor::Center,
+        Vec2::ZERO,
+        160.0,
+        Color::GOLD,
+        &text,
+    );
+    commands.entity(text).insert(TraitPickEntity);
+}
+
+fn close_trait_pick(mut commands: Commands) {
+    commands.remove_resource::<TraitPick>();
+}
+
+fn despawn_trait_pick(mut commands: Commands, trait_pick_q: Query<Entity, With<TraitPickEntity>>) {
+    trait_pick_q.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
@@ -1,14 +1,132 @@
 use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+use crate::camera::{MainCamera, PLAYFIELD_SIZE};
 
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system_to_stage(StartupStage::PreStartup, load_graphics);
+        app.add_startup_system_to_stage(StartupStage::PreStartup, load_graphics)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                anchor_hud.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+pub const LETTER_TILE_WIDTH: f32 = 8.0;
+
+/// Point on the screen a HUD element is anchored to
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Anchor {
+    Top,
+    TopRight,
+    Center,
+    Bottom,
+}
+
+impl Anchor {
+    /// Position of the anchor on the screen, from `-1.0` to `1.0` on both axes
+    fn point(&self) -> Vec2 {
+        match self {
+            Anchor::Top => Vec2::new(0.0, 1.0),
+            Anchor::TopRight => Vec2::new(1.0, 1.0),
+            Anchor::Center => Vec2::ZERO,
+            Anchor::Bottom => Vec2::new(0.0, -1.0),
+        }
+    }
+}
+
+/// HUD elements with this component stay anchored to the screen edges of the `MainCamera`,
+/// instead of staying at a world position.
+#[derive(Component)]
+pub struct HudAnchor {
+    pub anchor: Anchor,
+    /// Offset from the anchor in playfield units
+    pub offset: Vec2,
+    pub z: f32,
+    /// Local point of the element that is placed on the anchor
+    pub pivot: Vec2,
+}
+
+impl HudAnchor {
+    /// Anchors a sprite by its center
+    pub fn new(anchor: Anchor, offset: Vec2, z: f32) -> Self {
+        Self {
+            anchor,
+            offset,
+            z,
+            pivot: Vec2::ZERO,
+        }
+    }
+
+    /// Anchors text written by `write_text`, so that its edges line up with the anchor
+    pub fn for_text(anchor: Anchor, offset: Vec2, z: f32, text: &str) -> Self {
+        let tile = LETTER_TILE_WIDTH / 16.0;
+        let size = text_size(text);
+        let point = anchor.point();
+
+        Self {
+            anchor,
+            offset,
+            z,
+            pivot: Vec2::new(
+                (point.x + 1.0) / 2.0 * size.x - tile / 2.0,
+                -(1.0 - point.y) / 2.0 * size.y + tile / 2.0,
+            ),
+        }
     }
 }
 
-const LETTER_TILE_WIDTH: f32 = 8.0;
+/// Size of the text in world units, once it is written by `write_text`
+pub fn text_size(text: &str) -> Vec2 {
+    let columns = text.lines().map(|line| line.chars().count()).max();
+    Vec2::new(columns.unwrap_or(0) as f32, text.lines().count() as f32) * LETTER_TILE_WIDTH / 16.0
+}
+
+/// Writes text anchored to the screen, see `HudAnchor`
+pub fn write_hud_text(
+    commands: &mut Commands,
+    texture: &AsciiSheet,
+    anchor: Anchor,
+    offset: Vec2,
+    z: f32,
+    color: Color,
+    text: &str,
+) -> Entity {
+    let text_entity = write_text(commands, texture, Vec3::ZERO, color, text);
+    commands
+        .entity(text_entity)
+        .insert(HudAnchor::for_text(anchor, offset, z, text));
+    text_entity
+}
+
+/// Moves the HUD elements to their anchors, scaled with the camera zoom
+fn anchor_hud(
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut hud_q: Query<(&HudAnchor, &mut Transform), Without<MainCamera>>,
+) {
+    let (camera_transform, projection) = match camera_q.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    let extents = Vec2::new(
+        projection.right - projection.left,
+        projection.top - projection.bottom,
+    ) * projection.scale;
+    let zoom = extents.y / PLAYFIELD_SIZE.y;
+
+    for (hud, mut transform) in hud_q.iter_mut() {
+        let position = camera_transform.translation.truncate()
+            + hud.anchor.point() * extents / 2.0
+            + (hud.offset - hud.pivot) * zoom;
+
+        transform.translation = position.extend(hud.z);
+        transform.scale = Vec3::new(zoom, zoom, 1.0);
+    }
+}
 
 /// Write Ascii text to the screen at `position`
 pub fn write_text(
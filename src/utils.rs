@@ -16,6 +16,14 @@ impl Health {
             max: hp,
         }
     }
+
+    /// How much of the health is left, from `0.0` to `1.0`
+    pub fn ratio(&self) -> f32 {
+        if self.max <= 0.0 {
+            return 0.0;
+        }
+        (self.current / self.max).clamp(0.0, 1.0)
+    }
 }
 
 #[derive(Component)]
@@ -25,12 +33,14 @@ pub struct Attack {
     pub spotting_range: f32,
 }
 
-#[derive(Component)]
+#[derive(Component, Copy, Clone)]
 pub enum BehaviourType {
-    ChasingClosest, // the entity will chase the closest enemy entity
+    ChasingClosest,  // the entity will chase the closest enemy entity
+    ChasingWeakest,  // the entity will chase the most wounded enemy entity
+    KeepingDistance, // the entity attacks from its attack range and backs off from closer enemies
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Bounds {
     pub x: (f32, f32),
     pub y: (f32, f32),